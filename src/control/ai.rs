@@ -0,0 +1,202 @@
+use crate::control::board::{Board, BoundaryPolicy, Bounds, Terrain};
+use crate::control::executor::{Command, Heading, MapPlace};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const HEADINGS: [Heading; 4] = [Heading::N, Heading::E, Heading::S, Heading::W];
+
+// 难度决定敌人追击玩家的积极程度
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    // 敌人每次行动时寻路追击（而不是随机游走）的概率
+    pub fn aggression(self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.3,
+            Difficulty::Normal => 0.6,
+            Difficulty::Hard => 0.9,
+        }
+    }
+}
+
+// 追击时下一步的动作
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Chase {
+    // 已在射击位置，转向玩家
+    Aim(Heading),
+    // 朝该方向前进一格
+    Step(Heading),
+}
+
+// (x, y) 沿 heading 的相邻格，出界时按边界策略折回或返回 None
+pub fn neighbor(bounds: Bounds, x: i32, y: i32, heading: Heading) -> Option<(i32, i32)> {
+    let (x, y) = match heading {
+        Heading::N => (x, y - 1),
+        Heading::E => (x + 1, y),
+        Heading::S => (x, y + 1),
+        Heading::W => (x - 1, y),
+    };
+    if bounds.contains(x, y) {
+        Some((x, y))
+    } else if bounds.policy == BoundaryPolicy::Wrap {
+        Some(bounds.wrap(x, y))
+    } else {
+        None
+    }
+}
+
+// 视线检查：沿行或列从 from 能否看到 target，砖块、钢块、基地和道具阻挡视线，树林中的目标看不到；
+// 能看到时返回 target 所在的方向
+pub fn line_of_sight(board: &Board, from: (i32, i32), target: (i32, i32)) -> Option<Heading> {
+    if board.terrain(target.0, target.1) == Some(Terrain::Forest) {
+        return None;
+    }
+    sight(board, from, target, |place| {
+        matches!(
+            place,
+            MapPlace::Block(_) | MapPlace::Steel | MapPlace::Base | MapPlace::Pickup(_)
+        )
+    })
+}
+
+// 火力线检查：从 from 开火能否打穿砖块命中 target，钢块和会吸收子弹的道具挡得住
+pub fn line_of_fire(board: &Board, from: (i32, i32), target: (i32, i32)) -> Option<Heading> {
+    sight(board, from, target, |place| {
+        matches!(place, MapPlace::Steel | MapPlace::Pickup(_))
+    })
+}
+
+// 沿四个方向查找 target，遇到 blocks 的格子为止
+fn sight(
+    board: &Board,
+    from: (i32, i32),
+    target: (i32, i32),
+    blocks: impl Fn(MapPlace) -> bool,
+) -> Option<Heading> {
+    let bounds = board.bounds();
+    let reach = bounds.width().max(bounds.height());
+    HEADINGS.into_iter().find(|heading| {
+        let (mut x, mut y) = from;
+        for _ in 0..reach {
+            let Some(next) = neighbor(bounds, x, y, *heading) else {
+                return false;
+            };
+            if next == target {
+                return true;
+            }
+            if board.get(next.0, next.1).is_some_and(&blocks) {
+                return false;
+            }
+            (x, y) = next;
+        }
+        false
+    })
+}
+
+// 按最短路径（BFS）接近到能看到 target 的位置，每次调用都按当前地图重新规划
+pub fn chase(board: &Board, from: (i32, i32), target: (i32, i32)) -> Option<Chase> {
+    approach(board, from, |cell| line_of_sight(board, cell, target))
+}
+
+// 按最短路径接近到能隔着砖块向 base 开火的位置
+pub fn siege(board: &Board, from: (i32, i32), base: (i32, i32)) -> Option<Chase> {
+    approach(board, from, |cell| line_of_fire(board, cell, base))
+}
+
+fn approach(
+    board: &Board,
+    from: (i32, i32),
+    aim: impl Fn((i32, i32)) -> Option<Heading>,
+) -> Option<Chase> {
+    if let Some(heading) = aim(from) {
+        return Some(Chase::Aim(heading));
+    }
+    first_step(board, from, |cell| aim(cell).is_some()).map(Chase::Step)
+}
+
+// 走到 goal 的最短路径上第一步的方向，已在 goal 或无路可走时返回 None
+pub fn path_to(board: &Board, from: (i32, i32), goal: (i32, i32)) -> Option<Heading> {
+    first_step(board, from, |cell| cell == goal)
+}
+
+// 在坦克能通行的格子上做 BFS，返回通往最近的满足 is_goal 的格子的第一步
+fn first_step(
+    board: &Board,
+    from: (i32, i32),
+    is_goal: impl Fn((i32, i32)) -> bool,
+) -> Option<Heading> {
+    let bounds = board.bounds();
+    // 记录到达每个格子时第一步走的方向
+    let mut first: HashMap<(i32, i32), Heading> = HashMap::from([(from, Heading::N)]);
+    let mut queue = VecDeque::from([from]);
+    while let Some((x, y)) = queue.pop_front() {
+        for heading in HEADINGS {
+            let Some(next) = neighbor(bounds, x, y, heading) else {
+                continue;
+            };
+            if first.contains_key(&next) || !board.passable(next.0, next.1) {
+                continue;
+            }
+            let step = if (x, y) == from {
+                heading
+            } else {
+                first[&(x, y)]
+            };
+            if is_goal(next) {
+                return Some(step);
+            }
+            first.insert(next, step);
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+// 从 current 转到 wanted 所需的一条转向指令，已朝向 wanted 时返回 None
+pub fn turn_command(current: Heading, wanted: Heading) -> Option<Command> {
+    if current.left() == wanted {
+        Some(Command::Left)
+    } else if current.right() == wanted {
+        Some(Command::Right)
+    } else if current.opposite() == wanted {
+        Some(Command::TurnRound)
+    } else {
+        None
+    }
+}
+
+// 从 from 出发只经过 open 的格子能到达的所有格子（包括 from）
+pub fn reachable(
+    bounds: Bounds,
+    from: (i32, i32),
+    open: impl Fn((i32, i32)) -> bool,
+) -> HashSet<(i32, i32)> {
+    let mut reached = HashSet::from([from]);
+    let mut queue = vec![from];
+    while let Some((x, y)) = queue.pop() {
+        for heading in HEADINGS {
+            if let Some(next) = neighbor(bounds, x, y, heading) {
+                if open(next) && reached.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+    }
+    reached
+}
+
+// cells 中的格子能否经过 open 的格子互相到达
+pub fn connected(bounds: Bounds, cells: &[(i32, i32)], open: impl Fn((i32, i32)) -> bool) -> bool {
+    let Some(&first) = cells.first() else {
+        return true;
+    };
+    let reached = reachable(bounds, first, open);
+    cells.iter().all(|cell| reached.contains(cell))
+}
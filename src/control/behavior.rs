@@ -0,0 +1,227 @@
+use crate::control::ai::{self, Chase, Difficulty};
+use crate::control::board::{Board, BoundaryPolicy};
+use crate::control::executor::{Command, Heading};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::fmt;
+use std::str::FromStr;
+
+// 守卫离开岗位追击玩家的范围（曼哈顿距离）
+pub const GUARD_RADIUS: i32 = 4;
+
+// 敌人决策时看到的只读信息
+pub struct EnemyView<'a> {
+    pub board: &'a Board,
+    pub position: (i32, i32),
+    pub heading: Heading,
+    pub player: (i32, i32),
+    // 还没被摧毁的基地
+    pub base: Option<(i32, i32)>,
+}
+
+impl EnemyView<'_> {
+    // 正前方的格子，出界时为 None
+    fn front(&self) -> Option<(i32, i32)> {
+        let (x, y) = self.position;
+        ai::neighbor(self.board.bounds(), x, y, self.heading)
+    }
+
+    fn front_is_free(&self) -> bool {
+        self.front().is_some_and(|(x, y)| self.board.passable(x, y))
+    }
+
+    // 能开火命中的目标所在的方向：先看玩家，再看能隔着砖块打到的基地
+    fn target_heading(&self) -> Option<Heading> {
+        ai::line_of_sight(self.board, self.position, self.player).or_else(|| {
+            self.base
+                .and_then(|base| ai::line_of_fire(self.board, self.position, base))
+        })
+    }
+
+    // 基地是否比玩家离得更近（曼哈顿距离）
+    fn base_is_closer(&self) -> bool {
+        let distance =
+            |(x, y): (i32, i32)| (x - self.position.0).abs() + (y - self.position.1).abs();
+        self.base
+            .is_some_and(|base| distance(base) < distance(self.player))
+    }
+}
+
+// 敌人的行为，每个敌人各持有一个
+pub trait EnemyBehavior {
+    // 每次 MoveEnemies 时给出一条指令，None 表示原地不动
+    fn next_command(&mut self, view: &EnemyView<'_>, rng: &mut StdRng) -> Option<Command>;
+
+    // 装填完毕时决定朝哪个方向开火，None 表示不开火；默认看到玩家或能打到基地就转向开火
+    fn aim(&self, view: &EnemyView<'_>) -> Option<Heading> {
+        view.target_heading()
+    }
+}
+
+// 随机游走
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RandomWalker;
+
+impl EnemyBehavior for RandomWalker {
+    fn next_command(&mut self, view: &EnemyView<'_>, rng: &mut StdRng) -> Option<Command> {
+        match rng.gen_range(0..6) {
+            0 | 3 | 4 | 5 => {
+                // 致命边界上会直接驶出地图；被挡住时随机右转或原地不动
+                let off_edge =
+                    view.front().is_none() && view.board.bounds().policy == BoundaryPolicy::Lethal;
+                if off_edge || view.front_is_free() {
+                    Some(Command::Move)
+                } else if rng.gen_bool(0.5) {
+                    Some(Command::Right)
+                } else {
+                    None
+                }
+            }
+            1 => Some(Command::Right),
+            _ => Some(Command::Left),
+        }
+    }
+}
+
+// 沿直线来回巡逻，被挡住就掉头
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Patroller;
+
+impl EnemyBehavior for Patroller {
+    fn next_command(&mut self, view: &EnemyView<'_>, _rng: &mut StdRng) -> Option<Command> {
+        if view.front_is_free() {
+            Some(Command::Move)
+        } else {
+            Some(Command::TurnRound)
+        }
+    }
+}
+
+// 按 aggression 的概率寻路追击玩家，其余时间随机游走
+#[derive(Debug, Copy, Clone)]
+pub struct Chaser {
+    pub aggression: f64,
+}
+
+impl Chaser {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Chaser {
+            aggression: difficulty.aggression(),
+        }
+    }
+
+    // 始终追击：找不到路时为 None，已瞄准目标时为 Some(None) 表示原地不动；
+    // 看不到玩家时，基地更近或追不到玩家就改为进攻基地
+    fn pursue(view: &EnemyView<'_>) -> Option<Option<Command>> {
+        let player = ai::chase(view.board, view.position, view.player);
+        let base = view
+            .base
+            .and_then(|base| ai::siege(view.board, view.position, base));
+        let chase = match (player, base) {
+            (Some(Chase::Aim(heading)), _) => Chase::Aim(heading),
+            (player, Some(base)) if player.is_none() || view.base_is_closer() => base,
+            (player, _) => player?,
+        };
+        Some(match chase {
+            Chase::Aim(heading) => ai::turn_command(view.heading, heading),
+            Chase::Step(heading) => step_towards(view.heading, heading),
+        })
+    }
+}
+
+impl EnemyBehavior for Chaser {
+    fn next_command(&mut self, view: &EnemyView<'_>, rng: &mut StdRng) -> Option<Command> {
+        if rng.gen_bool(self.aggression) {
+            if let Some(cmd) = Chaser::pursue(view) {
+                return cmd;
+            }
+        }
+        RandomWalker.next_command(view, rng)
+    }
+}
+
+// 原地不动，只向正前方开火
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Sniper;
+
+impl EnemyBehavior for Sniper {
+    fn next_command(&mut self, _view: &EnemyView<'_>, _rng: &mut StdRng) -> Option<Command> {
+        None
+    }
+
+    fn aim(&self, view: &EnemyView<'_>) -> Option<Heading> {
+        view.target_heading()
+            .filter(|heading| *heading == view.heading)
+    }
+}
+
+// 守在岗位附近，玩家靠近时追击，玩家离开后返回岗位
+#[derive(Debug, Copy, Clone)]
+pub struct Guard {
+    pub post: (i32, i32),
+}
+
+impl EnemyBehavior for Guard {
+    fn next_command(&mut self, view: &EnemyView<'_>, _rng: &mut StdRng) -> Option<Command> {
+        let (px, py) = view.player;
+        let (gx, gy) = self.post;
+        if (px - gx).abs() + (py - gy).abs() <= GUARD_RADIUS {
+            return Chaser::pursue(view).flatten();
+        }
+        let heading = ai::path_to(view.board, view.position, self.post)?;
+        step_towards(view.heading, heading)
+    }
+}
+
+// 朝 heading 前进一步：先转向，已朝向时前进
+fn step_towards(current: Heading, heading: Heading) -> Option<Command> {
+    ai::turn_command(current, heading).or(Some(Command::Move))
+}
+
+// 关卡数据中用来指定敌人行为的名称
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum BehaviorKind {
+    RandomWalker,
+    Patroller,
+    #[default]
+    Chaser,
+    Sniper,
+    Guard,
+}
+
+impl BehaviorKind {
+    pub const ALL: [BehaviorKind; 5] = [
+        BehaviorKind::RandomWalker,
+        BehaviorKind::Patroller,
+        BehaviorKind::Chaser,
+        BehaviorKind::Sniper,
+        BehaviorKind::Guard,
+    ];
+
+    // 为出生在 post 的敌人生成行为
+    pub fn build(self, post: (i32, i32), difficulty: Difficulty) -> Box<dyn EnemyBehavior> {
+        match self {
+            BehaviorKind::RandomWalker => Box::new(RandomWalker),
+            BehaviorKind::Patroller => Box::new(Patroller),
+            BehaviorKind::Chaser => Box::new(Chaser::new(difficulty)),
+            BehaviorKind::Sniper => Box::new(Sniper),
+            BehaviorKind::Guard => Box::new(Guard { post }),
+        }
+    }
+}
+
+impl fmt::Display for BehaviorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl FromStr for BehaviorKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BehaviorKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or_else(|| format!("unknown behavior {:?}", s))
+    }
+}
@@ -0,0 +1,210 @@
+use crate::control::executor::MapPlace;
+use std::ops::{Index, IndexMut};
+
+// 单位或子弹越过地图边缘时的处理方式
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum BoundaryPolicy {
+    // 停在边缘
+    #[default]
+    Clamp,
+    // 首尾相接的环面地图
+    Wrap,
+    // 驶出边缘即被摧毁
+    Lethal,
+}
+
+// 单位和子弹脚下的地形
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Terrain {
+    #[default]
+    Ground,
+    // 坦克不能进入，子弹可以飞过
+    Water,
+    // 遮住其中的单位，敌人看不到也不会显示
+    Forest,
+    // 坦克停在冰面上时会再滑行一格
+    Ice,
+}
+
+impl Terrain {
+    pub const ALL: [Terrain; 4] = [
+        Terrain::Ground,
+        Terrain::Water,
+        Terrain::Forest,
+        Terrain::Ice,
+    ];
+
+    // 地图和关卡文件中的字符
+    pub fn glyph(self) -> char {
+        match self {
+            Terrain::Ground => '+',
+            Terrain::Water => '~',
+            Terrain::Forest => '%',
+            Terrain::Ice => '_',
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Bounds {
+    // 坐标范围（闭区间），原点位于地图中心，y 轴向下增长
+    pub x_min: i32,
+    pub x_max: i32,
+    pub y_min: i32,
+    pub y_max: i32,
+    pub policy: BoundaryPolicy,
+}
+
+impl BoundaryPolicy {
+    pub const ALL: [BoundaryPolicy; 3] = [
+        BoundaryPolicy::Clamp,
+        BoundaryPolicy::Wrap,
+        BoundaryPolicy::Lethal,
+    ];
+}
+
+impl Bounds {
+    // 按宽高生成以原点为中心的范围，偶数边长时多出的一格放在正方向
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "board must not be empty");
+        let x_min = -((width as i32 - 1) / 2);
+        let y_min = -((height as i32 - 1) / 2);
+        Bounds {
+            x_min,
+            x_max: x_min + width as i32 - 1,
+            y_min,
+            y_max: y_min + height as i32 - 1,
+            policy: BoundaryPolicy::Clamp,
+        }
+    }
+
+    pub fn with_policy(self, policy: BoundaryPolicy) -> Self {
+        Bounds { policy, ..self }
+    }
+
+    pub fn width(&self) -> usize {
+        (self.x_max - self.x_min + 1) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.y_max - self.y_min + 1) as usize
+    }
+
+    pub fn area(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x_min..=self.x_max).contains(&x) && (self.y_min..=self.y_max).contains(&y)
+    }
+
+    // 把越界坐标折回到地图另一侧
+    pub fn wrap(&self, x: i32, y: i32) -> (i32, i32) {
+        (
+            self.x_min + (x - self.x_min).rem_euclid(self.width() as i32),
+            self.y_min + (y - self.y_min).rem_euclid(self.height() as i32),
+        )
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if self.contains(x, y) {
+            Some((y - self.y_min) as usize * self.width() + (x - self.x_min) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds::new(13, 11)
+    }
+}
+
+// 两层地图：cells 为单位、子弹和障碍物，ground 为其下的地形
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    bounds: Bounds,
+    cells: Vec<MapPlace>,
+    ground: Vec<Terrain>,
+}
+
+impl Board {
+    pub fn new(bounds: Bounds) -> Self {
+        Board {
+            bounds,
+            cells: vec![MapPlace::Place; bounds.area()],
+            ground: vec![Terrain::Ground; bounds.area()],
+        }
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<MapPlace> {
+        self.bounds.index(x, y).map(|i| self.cells[i])
+    }
+
+    pub fn terrain(&self, x: i32, y: i32) -> Option<Terrain> {
+        self.bounds.index(x, y).map(|i| self.ground[i])
+    }
+
+    pub fn set_terrain(&mut self, x: i32, y: i32, terrain: Terrain) {
+        if let Some(i) = self.bounds.index(x, y) {
+            self.ground[i] = terrain;
+        }
+    }
+
+    // 坦克能否进入 (x, y)：空地且不是水面
+    pub fn passable(&self, x: i32, y: i32) -> bool {
+        self.get(x, y) == Some(MapPlace::Place) && self.terrain(x, y) != Some(Terrain::Water)
+    }
+
+    // 玩家能否进入 (x, y)：空地之外还能开到道具上
+    pub fn player_passable(&self, x: i32, y: i32) -> bool {
+        self.passable(x, y) || matches!(self.get(x, y), Some(MapPlace::Pickup(_)))
+    }
+
+    // 地形或障碍物挡住坦克的格子，出界也算
+    pub fn is_obstacle(&self, x: i32, y: i32) -> bool {
+        matches!(
+            self.get(x, y),
+            None | Some(
+                MapPlace::Block(_) | MapPlace::Steel | MapPlace::Base | MapPlace::Pickup(_)
+            )
+        ) || self.terrain(x, y) == Some(Terrain::Water)
+    }
+
+    // 按行（从上到下）遍历，便于渲染
+    pub fn rows(&self) -> std::slice::Chunks<'_, MapPlace> {
+        self.cells.chunks(self.bounds.width())
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new(Bounds::default())
+    }
+}
+
+impl Index<(i32, i32)> for Board {
+    type Output = MapPlace;
+    fn index(&self, (x, y): (i32, i32)) -> &MapPlace {
+        let i = self
+            .bounds
+            .index(x, y)
+            .unwrap_or_else(|| panic!("({}, {}) is outside the board", x, y));
+        &self.cells[i]
+    }
+}
+
+impl IndexMut<(i32, i32)> for Board {
+    fn index_mut(&mut self, (x, y): (i32, i32)) -> &mut MapPlace {
+        let i = self
+            .bounds
+            .index(x, y)
+            .unwrap_or_else(|| panic!("({}, {}) is outside the board", x, y));
+        &mut self.cells[i]
+    }
+}
@@ -0,0 +1,182 @@
+use crate::control::behavior::BehaviorKind;
+use crate::control::board::{Bounds, Terrain};
+use crate::control::executor::BLOCK_HP;
+use crate::control::level::{Level, SpawnPoint, MAX_BLOCK_HP};
+
+// 编辑器中点击格子时画上的内容
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Brush {
+    Floor,
+    // 砖块
+    Block,
+    Steel,
+    // 水面、树林、冰面等地形，Ground 与 Floor 相同
+    Terrain(Terrain),
+    // 敌人出生点，None 表示由波次决定行为
+    EnemySpawn(Option<BehaviorKind>),
+    // 额外的玩家重生点
+    PlayerSpawn,
+    // 玩家开局位置，再次点击同一格会转向
+    PlayerStart,
+    // 基地，每张地图最多一个
+    Base,
+}
+
+impl Brush {
+    pub const ALL: [Brush; 15] = [
+        Brush::Floor,
+        Brush::Block,
+        Brush::Steel,
+        Brush::Terrain(Terrain::Water),
+        Brush::Terrain(Terrain::Forest),
+        Brush::Terrain(Terrain::Ice),
+        Brush::PlayerStart,
+        Brush::PlayerSpawn,
+        Brush::Base,
+        Brush::EnemySpawn(None),
+        Brush::EnemySpawn(Some(BehaviorKind::RandomWalker)),
+        Brush::EnemySpawn(Some(BehaviorKind::Patroller)),
+        Brush::EnemySpawn(Some(BehaviorKind::Chaser)),
+        Brush::EnemySpawn(Some(BehaviorKind::Sniper)),
+        Brush::EnemySpawn(Some(BehaviorKind::Guard)),
+    ];
+
+    pub fn name(self) -> String {
+        match self {
+            Brush::EnemySpawn(None) => "Enemy".to_string(),
+            Brush::EnemySpawn(Some(kind)) => kind.to_string(),
+            Brush::Block => "Brick".to_string(),
+            Brush::Terrain(terrain) => format!("{:?}", terrain),
+            _ => format!("{:?}", self),
+        }
+    }
+}
+
+// 不依赖 GUI 的关卡编辑器，支持撤销和重做
+#[derive(Debug, Clone)]
+pub struct Editor {
+    level: Level,
+    undo: Vec<Level>,
+    redo: Vec<Level>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor::new(Level::new(Bounds::default()))
+    }
+}
+
+impl Editor {
+    pub fn new(level: Level) -> Self {
+        Editor {
+            level,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    // 在 (x, y) 上画 brush，地图外的格子忽略
+    pub fn paint(&mut self, x: i32, y: i32, brush: Brush) {
+        if !self.level.bounds.contains(x, y) {
+            return;
+        }
+        let mut level = self.level.clone();
+        if brush == Brush::PlayerStart && level.spawns.player.first() == Some(&(x, y)) {
+            level.heading = level.heading.right();
+            self.commit(level);
+            return;
+        }
+        // 再次点击同一块砖会在 1 到 MAX_BLOCK_HP 之间循环改变耐久度
+        if brush == Brush::Block {
+            if let Some((_, hp)) = level.blocks.iter_mut().find(|(cell, _)| *cell == (x, y)) {
+                *hp = *hp % MAX_BLOCK_HP + 1;
+                self.commit(level);
+                return;
+            }
+        }
+        // 先清空这一格，开局位置被清掉时由下一个重生点顶替
+        level.blocks.retain(|(cell, _)| *cell != (x, y));
+        level.steel.retain(|cell| *cell != (x, y));
+        level.terrain.retain(|(cell, _)| *cell != (x, y));
+        if level.base == Some((x, y)) {
+            level.base = None;
+        }
+        level.spawns.enemies.retain(|p| (p.x, p.y) != (x, y));
+        level.spawns.player.retain(|cell| *cell != (x, y));
+        match brush {
+            Brush::Floor => {}
+            Brush::Block => level.blocks.push(((x, y), BLOCK_HP)),
+            Brush::Steel => level.steel.push((x, y)),
+            Brush::Terrain(Terrain::Ground) => {}
+            Brush::Terrain(terrain) => level.terrain.push(((x, y), terrain)),
+            Brush::EnemySpawn(behavior) => level.spawns.enemies.push(SpawnPoint { x, y, behavior }),
+            Brush::PlayerSpawn => level.spawns.player.push((x, y)),
+            // 原来的基地变成空地
+            Brush::Base => level.base = Some((x, y)),
+            Brush::PlayerStart => {
+                // 原来的开局位置变成空地
+                if !level.spawns.player.is_empty() {
+                    level.spawns.player.remove(0);
+                }
+                level.spawns.player.insert(0, (x, y));
+            }
+        }
+        self.commit(level);
+    }
+
+    // 改变地图大小，超出新范围的内容被丢弃
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let bounds = Bounds::new(width, height).with_policy(self.level.bounds.policy);
+        let mut level = self.level.clone();
+        level.bounds = bounds;
+        level.blocks.retain(|((x, y), _)| bounds.contains(*x, *y));
+        level.steel.retain(|(x, y)| bounds.contains(*x, *y));
+        level.terrain.retain(|((x, y), _)| bounds.contains(*x, *y));
+        level.base = level.base.filter(|(x, y)| bounds.contains(*x, *y));
+        level.spawns.enemies.retain(|p| bounds.contains(p.x, p.y));
+        level.spawns.player.retain(|(x, y)| bounds.contains(*x, *y));
+        self.commit(level);
+    }
+
+    // 替换整个关卡（例如修改设置），同样可以撤销
+    pub fn set_level(&mut self, level: Level) {
+        self.commit(level);
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(level) = self.undo.pop() else {
+            return false;
+        };
+        self.redo.push(std::mem::replace(&mut self.level, level));
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(level) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push(std::mem::replace(&mut self.level, level));
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // 记录一次修改，没有变化时不产生撤销记录
+    fn commit(&mut self, level: Level) {
+        if level == self.level {
+            return;
+        }
+        self.undo.push(std::mem::replace(&mut self.level, level));
+        self.redo.clear();
+    }
+}
@@ -1,391 +1,1315 @@
+use crate::control::ai::{self, Difficulty};
+use crate::control::behavior::{BehaviorKind, EnemyBehavior, EnemyView};
+use crate::control::board::{Board, BoundaryPolicy, Bounds, Terrain};
 use crate::control::executor::MapPlace::Player;
-use rand::Rng;
-use std::ops::Add;
+use crate::control::generator::{keeps_connected, Generator};
+use crate::control::level::{Level, SpawnPoint, Spawns, SPAWN_SAFE_DISTANCE};
+use crate::control::play::AppMessage;
+use crate::control::powerup::{PowerUp, MAX_PICKUPS, PICKUP_DROP_CHANCE};
+use crate::control::wave::{Wave, WAVE_BONUS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use std::ops::{Add, RangeInclusive};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
 
-pub const X_MAX: i32 = 6;
-pub const Y_MAX: i32 = 5;
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Heading {
+    // 屏幕上方为北，y 轴向下增长
+    #[default]
+    N,
+    E,
+    S,
+    W,
+}
+
+impl Heading {
+    pub fn left(self) -> Self {
+        match self {
+            Heading::E => Heading::N,
+            Heading::N => Heading::W,
+            Heading::W => Heading::S,
+            Heading::S => Heading::E,
+        }
+    }
+
+    pub fn right(self) -> Self {
+        match self {
+            Heading::E => Heading::S,
+            Heading::S => Heading::W,
+            Heading::W => Heading::N,
+            Heading::N => Heading::E,
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        self.left().left()
+    }
+
+    // GUI 中表示朝向的字符
+    pub fn glyph(self) -> char {
+        match self {
+            Heading::E => '>',
+            Heading::S => 'v',
+            Heading::W => '<',
+            Heading::N => '^',
+        }
+    }
+}
+
+impl fmt::Display for Heading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            Heading::N => 'N',
+            Heading::E => 'E',
+            Heading::S => 'S',
+            Heading::W => 'W',
+        };
+        write!(f, "{}", c)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHeadingError(pub String);
+
+impl fmt::Display for ParseHeadingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid heading: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseHeadingError {}
+
+impl FromStr for Heading {
+    type Err = ParseHeadingError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "N" => Ok(Heading::N),
+            "E" => Ok(Heading::E),
+            "S" => Ok(Heading::S),
+            "W" => Ok(Heading::W),
+            _ => Err(ParseHeadingError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Command {
+    Move,
+    Left,
+    Right,
+    // 原地掉头
+    TurnRound,
+    // 切换加速模式：每次移动前进两格
+    Fast,
+    // 切换倒车模式：移动指令改为后退
+    Back,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Command::Move => "M",
+            Command::Left => "L",
+            Command::Right => "R",
+            Command::TurnRound => "TR",
+            Command::Fast => "F",
+            Command::Back => "B",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCommandError(pub String);
+
+impl fmt::Display for ParseCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid command: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseCommandError {}
+
+impl FromStr for Command {
+    type Err = ParseCommandError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "M" => Ok(Command::Move),
+            "L" => Ok(Command::Left),
+            "R" => Ok(Command::Right),
+            "TR" => Ok(Command::TurnRound),
+            "F" => Ok(Command::Fast),
+            "B" => Ok(Command::Back),
+            _ => Err(ParseCommandError(s.to_string())),
+        }
+    }
+}
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramError {
+    // 出错字符在指令串中的位置（按字符计数，从0开始）
+    pub index: usize,
+    pub found: char,
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid command {:?} at index {}",
+            self.found, self.index
+        )
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+impl Command {
+    pub const ALL: [Command; 6] = [
+        Command::Move,
+        Command::Left,
+        Command::Right,
+        Command::TurnRound,
+        Command::Fast,
+        Command::Back,
+    ];
+
+    // 逐字符解析整段指令串，每条指令交给 FromStr 识别，遇到无法识别的字符立即报错
+    pub fn parse_program(program: &str) -> Result<Vec<Command>, ProgramError> {
+        let mut cmds = Vec::with_capacity(program.len());
+        // 正在读取的指令及其第一个字符的位置，TR 这样的指令由多个字符组成
+        let mut token = String::new();
+        let mut start = 0;
+        for (index, found) in program.chars().enumerate() {
+            if token.is_empty() {
+                start = index;
+            }
+            token.push(found);
+            if !Command::ALL
+                .iter()
+                .any(|cmd| cmd.to_string().starts_with(token.as_str()))
+            {
+                return Err(ProgramError { index, found });
+            }
+            if let Ok(cmd) = token.parse() {
+                cmds.push(cmd);
+                token.clear();
+            }
+        }
+        // 以不完整的指令结尾，例如单独的 T
+        match token.chars().next() {
+            Some(found) => Err(ProgramError {
+                index: start,
+                found,
+            }),
+            None => Ok(cmds),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub struct Pose {
     // 在计算和计算机视觉领域，pose表示位置和方向
     pub x: i32,
     pub y: i32,
-    pub heading: char,
-    is_shoot: bool,
+    pub heading: Heading,
+    bounds: Bounds,
 } // 定义结构体，便于数据的组织和传递
 impl Pose {
-    // 提供初始化到指定位置(x, y, heading)的能力
-    pub fn new(x: i32, y: i32, heading: char) -> Self {
+    // 提供初始化到指定位置(x, y, heading)的能力，使用默认大小的地图边界
+    pub fn new(x: i32, y: i32, heading: Heading) -> Self {
         Pose {
             x,
             y,
             heading,
-            is_shoot: false,
+            bounds: Bounds::default(),
         }
     }
 
+    // 指定移动时遵守的地图边界
+    pub fn with_bounds(self, bounds: Bounds) -> Self {
+        Pose { bounds, ..self }
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
     fn left(self) -> Self {
         Pose {
-            x: self.x,
-            y: self.y,
-            heading: match self.heading {
-                'E' => 'N',
-                'N' => 'W',
-                'W' => 'S',
-                'S' => 'E',
-                _ => 'N',
-            },
-            is_shoot: false,
+            heading: self.heading.left(),
+            ..self
         }
     }
 
     fn right(self) -> Self {
         Pose {
-            x: self.x,
-            y: self.y,
-            heading: match self.heading {
-                'E' => 'S',
-                'S' => 'W',
-                'W' => 'N',
-                'N' => 'E',
-                _ => 'N',
-            },
-            is_shoot: false,
+            heading: self.heading.right(),
+            ..self
         }
     }
-}
 
-impl Add for Pose {
-    type Output = Pose;
-    fn add(self, other: Pose) -> Pose {
-        match self.heading {
-            'E' => Pose::new(
-                if self.x < X_MAX {
-                    self.x + other.x
-                } else {
-                    self.x
-                },
-                self.y,
-                self.heading,
-            ),
-            'W' => Pose::new(
-                if self.x > -X_MAX {
-                    self.x - other.x
-                } else {
-                    self.x
-                },
-                self.y,
-                self.heading,
-            ),
-            'S' => Pose::new(
-                self.x,
-                if self.y < Y_MAX {
-                    self.y + other.y
-                } else {
-                    self.y
-                },
-                self.heading,
-            ),
-            'N' => Pose::new(
-                self.x,
-                if self.y > -Y_MAX {
-                    self.y - other.y
-                } else {
-                    self.y
-                },
-                self.heading,
-            ),
-            _ => Pose::new(self.x, self.y, self.heading),
+    fn turn_round(self) -> Self {
+        Pose {
+            heading: self.heading.opposite(),
+            ..self
         }
     }
-}
 
-impl Default for Pose {
-    fn default() -> Self {
+    // 沿朝向前进一格，边界检查由 Add 完成
+    fn forward(self) -> Self {
+        self + Pose::new(1, 1, Heading::N)
+    }
+
+    // 保持朝向后退一格
+    fn backward(self) -> Self {
         Pose {
-            x: 0,
-            y: 0,
-            heading: 'N',
-            is_shoot: false,
+            heading: self.heading,
+            ..self.turn_round().forward()
         }
     }
 }
 
+impl Add for Pose {
+    type Output = Pose;
+    fn add(self, other: Pose) -> Pose {
+        let bounds = self.bounds;
+        let (x, y) = match self.heading {
+            Heading::E => (self.x + other.x, self.y),
+            Heading::W => (self.x - other.x, self.y),
+            Heading::S => (self.x, self.y + other.y),
+            Heading::N => (self.x, self.y - other.y),
+        };
+        let (x, y) = match bounds.policy {
+            // 到达边界后停在原地
+            BoundaryPolicy::Clamp => (
+                x.clamp(bounds.x_min, bounds.x_max),
+                y.clamp(bounds.y_min, bounds.y_max),
+            ),
+            // 从一侧出界后从对侧进入
+            BoundaryPolicy::Wrap => bounds.wrap(x, y),
+            // 保留出界坐标，由 Executor 负责销毁
+            BoundaryPolicy::Lethal => (x, y),
+        };
+        Pose { x, y, ..self }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub struct Executor {
     pose: Option<Pose>,
+    fast: bool,
+    reverse: bool,
 }
 
 impl Executor {
     pub fn with_pose(pose: Pose) -> Self {
-        Executor { pose: Some(pose) }
-    }
-
-    pub fn execute(&mut self, cmds: &str) {
-        if let Some(pose) = self.pose {
-            self.pose = Some(match cmds {
-                "M" => {
-                    pose + Pose {
-                        x: 1,
-                        y: 1,
-                        heading: 'N',
-                        is_shoot: false,
+        Executor {
+            pose: Some(pose),
+            fast: false,
+            reverse: false,
+        }
+    }
+
+    // 执行整段指令串，返回每一步之后的位姿；指令串有误时不执行任何一步
+    pub fn execute(&mut self, cmds: &str) -> Result<Vec<Pose>, ProgramError> {
+        let cmds = Command::parse_program(cmds)?;
+        let mut trace = Vec::with_capacity(cmds.len());
+        for cmd in cmds {
+            self.apply(cmd);
+            if let Some(pose) = self.pose {
+                trace.push(pose);
+            }
+        }
+        Ok(trace)
+    }
+
+    pub fn apply(&mut self, cmd: Command) {
+        self.apply_with(cmd, |_, _| true);
+    }
+
+    // 执行单条指令，passable 判断某一格能否进入；
+    // 加速模式下的每一小步都单独检查，撞到障碍或边界时停在半路
+    pub fn apply_with(&mut self, cmd: Command, mut passable: impl FnMut(i32, i32) -> bool) {
+        if let Some(mut pose) = self.pose {
+            match cmd {
+                Command::Move => {
+                    let steps = if self.fast { 2 } else { 1 };
+                    for _ in 0..steps {
+                        let next = if self.reverse {
+                            pose.backward()
+                        } else {
+                            pose.forward()
+                        };
+                        if !pose.bounds.contains(next.x, next.y) {
+                            // 致命边界：驶出地图即被摧毁
+                            self.pose = None;
+                            return;
+                        }
+                        if (next.x, next.y) == (pose.x, pose.y) || !passable(next.x, next.y) {
+                            break;
+                        }
+                        pose = next;
                     }
                 }
-                "L" => pose.left(),
-                "R" => pose.right(),
-                _ => pose,
-            });
+                Command::Left => pose = pose.left(),
+                Command::Right => pose = pose.right(),
+                Command::TurnRound => pose = pose.turn_round(),
+                Command::Fast => self.fast = !self.fast,
+                Command::Back => self.reverse = !self.reverse,
+            }
+            self.pose = Some(pose);
         }
     }
 
-    pub fn query(&self) -> (i32, i32, char) {
-        let x = self.pose.unwrap().x;
-        let y = self.pose.unwrap().y;
-        let heading = self.pose.unwrap().heading;
-        (x, y, heading)
+    // 被摧毁后返回 None
+    pub fn pose(&self) -> Option<Pose> {
+        self.pose
+    }
+
+    pub fn is_fast(&self) -> bool {
+        self.fast
+    }
+
+    pub fn is_reverse(&self) -> bool {
+        self.reverse
+    }
+
+    // 当前的位置和朝向，驶出致命边界被摧毁后为 None
+    pub fn query(&self) -> Option<(i32, i32, Heading)> {
+        self.pose.map(|pose| (pose.x, pose.y, pose.heading))
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub enum MapPlace {
     Player(Executor),
     Enemy(Executor),
     Shoot(Executor),
+    #[default]
     Place,
-    Block,
+    // 砖块及其剩余耐久度，每被击中一次减一，耗尽时被摧毁
+    Block(u32),
+    // 钢块，子弹打不掉
+    Steel,
+    // 道具，只有玩家能开上去拾取；对敌人、子弹、视线和寻路来说与障碍物一样，
+    // 子弹打中时被吸收，道具不受影响
+    Pickup(PowerUp),
+    // 要保护的基地，被任何子弹击中即输掉游戏
+    Base,
 }
 
-impl Default for MapPlace {
-    fn default() -> Self {
-        MapPlace::Place
-    }
+// 子弹的位置和剩余射程；射程只在环绕边界下起作用，防止子弹无限绕圈
+#[derive(Copy, Clone)]
+struct Shot {
+    x: i32,
+    y: i32,
+    range: usize,
+}
+
+// 玩家的生命值和命数，敌人的生命值
+pub const PLAYER_HP: u32 = 3;
+pub const PLAYER_LIVES: u32 = 3;
+pub const ENEMY_HP: u32 = 2;
+// 砖块默认的耐久度
+pub const BLOCK_HP: u32 = 3;
+// 被击中后的无敌时间，以子弹移动（MoveShoot）的步数计
+pub const INVULNERABLE_STEPS: u32 = 3;
+
+// 敌人两次开火之间间隔的 Shoot 事件数，每个敌人各自随机，默认节奏下约 3 到 6 秒
+const ENEMY_RELOAD: RangeInclusive<u32> = 6..=12;
+
+// 敌人的位置、生命值、各自的开火倒计时和行为
+struct EnemyState {
+    x: i32,
+    y: i32,
+    hp: u32,
+    reload: u32,
+    kind: BehaviorKind,
+    post: (i32, i32),
+    behavior: Box<dyn EnemyBehavior>,
 }
 
-#[derive(Default)]
 pub struct Executors {
-    pub executors: [[MapPlace; (2 * X_MAX + 1) as usize]; (2 * Y_MAX + 1) as usize],
+    pub executors: Board,
     player_x: i32,
     player_y: i32,
-    enemy_place: Vec<(i32, i32)>,
-    shoot_place: Vec<(i32, i32)>,
+    player_hp: u32,
+    lives: u32,
+    invulnerable: u32,
+    enemy_place: Vec<EnemyState>,
+    spawns: Spawns,
+    // 玩家开局的朝向
+    heading: Heading,
+    // 下一次刷新从哪个出生点开始，轮流使用各个出生点
+    spawn_cursor: usize,
+    // 当前波次和本波尚未出场的敌人
+    wave: u32,
+    to_spawn: VecDeque<BehaviorKind>,
+    shoot_place: Vec<Shot>,
     pub point: i32,
     pub is_lose: bool,
+    // 生效中的道具及其剩余时间
+    effects: Vec<(PowerUp, Duration)>,
+    difficulty: Difficulty,
+    // 从关卡文件加载时的原始关卡，重开时据此重建地图
+    level: Option<Level>,
+    generator: Generator,
+    // 基地的位置，没有基地时为 None
+    base: Option<(i32, i32)>,
+    // 砖块出现时的耐久度，用来显示损坏程度
+    block_durability: HashMap<(i32, i32), u32>,
+    // 一局游戏中所有随机行为共用的随机数发生器，相同种子和输入得到相同的对局
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Default for Executors {
+    fn default() -> Self {
+        Executors::new()
+    }
 }
 
 impl Executors {
     pub fn new() -> Self {
-        let mut executors = [[MapPlace::Place; (2 * X_MAX + 1) as usize]; (2 * Y_MAX + 1) as usize];
-        let mut enemy_place = Vec::new();
-        for _ in 0..10 {
-            let mut rng = rand::thread_rng();
-            let block_x = rng.gen_range(0..13);
-            let block_y = rng.gen_range(0..11);
-            executors[block_y][block_x] = MapPlace::Block;
-        }
-        executors[(0 + Y_MAX + Y_MAX) as usize][(0 + X_MAX) as usize] =
-            Player(Executor::with_pose(Pose::new(0, Y_MAX, 'N')));
-        executors[(-Y_MAX + Y_MAX) as usize][(X_MAX - 2 + X_MAX) as usize] =
-            MapPlace::Enemy(Executor::with_pose(Pose::new(X_MAX - 2, -Y_MAX, 'S')));
-        enemy_place.push((X_MAX - 2, -Y_MAX));
-        executors[(-Y_MAX + Y_MAX) as usize][(-X_MAX + 2 + X_MAX) as usize] =
-            MapPlace::Enemy(Executor::with_pose(Pose::new(-X_MAX + 2, -Y_MAX, 'S')));
-        enemy_place.push((-X_MAX + 2, -Y_MAX));
-        Executors {
+        Executors::with_bounds(Bounds::default())
+    }
+
+    // 随机选取种子，可通过 seed() 取回以复现对局
+    pub fn with_bounds(bounds: Bounds) -> Self {
+        Executors::with_seed(bounds, rand::random())
+    }
+
+    pub fn with_seed(bounds: Bounds, seed: u64) -> Self {
+        Executors::with_spawns(bounds, seed, Spawns::new(bounds))
+    }
+
+    // 使用指定的出生点布局，玩家开局出生在第一个玩家出生点
+    pub fn with_spawns(bounds: Bounds, seed: u64, spawns: Spawns) -> Self {
+        Executors::with_generator(bounds, seed, spawns, Generator::default())
+    }
+
+    // 用指定的生成器生成地图，所有出生点都能互相到达
+    pub fn with_generator(bounds: Bounds, seed: u64, spawns: Spawns, generator: Generator) -> Self {
+        assert!(
+            spawns.player.iter().any(|(x, y)| bounds.contains(*x, *y)),
+            "no player spawn point inside the board"
+        );
+        let mut executors = Board::new(bounds);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let keys = spawn_cells(bounds, &spawns);
+        for (x, y) in generator.generate(bounds, &keys, &mut rng) {
+            executors[(x, y)] = MapPlace::Block(BLOCK_HP);
+        }
+        let mut map = Executors::build(executors, rng, seed, spawns, Heading::N);
+        map.generator = generator;
+        map
+    }
+
+    // 按关卡文件的地图开局，敌人和方块刷新等随机行为仍由 seed 决定
+    pub fn from_level(level: Level, seed: u64) -> Self {
+        let mut executors = Board::new(level.bounds);
+        for ((x, y), hp) in &level.blocks {
+            executors[(*x, *y)] = MapPlace::Block(*hp);
+        }
+        for (x, y) in &level.steel {
+            executors[(*x, *y)] = MapPlace::Steel;
+        }
+        for ((x, y), terrain) in &level.terrain {
+            executors.set_terrain(*x, *y, *terrain);
+        }
+        if let Some((x, y)) = level.base {
+            executors[(x, y)] = MapPlace::Base;
+        }
+        let rng = StdRng::seed_from_u64(seed);
+        let mut map = Executors::build(executors, rng, seed, level.spawns.clone(), level.heading);
+        map.base = level.base;
+        map.set_difficulty(level.difficulty);
+        map.level = Some(level);
+        map
+    }
+
+    // 读取关卡文件，格式错误时给出行号和列号，无法游玩的关卡同样拒绝读取
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let level = Level::load(path)?;
+        level
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Executors::from_level(level, rand::random()))
+    }
+
+    // 把当前地图写成关卡文件，与读取时一样拒绝无法游玩的关卡
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let level = self.to_level();
+        level
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        level.save(path)
+    }
+
+    // 当前地图的快照：方块和地形照当前的样子，玩家仍从原来的开局位置和朝向出发
+    pub fn to_level(&self) -> Level {
+        let bounds = self.bounds();
+        let mut blocks = Vec::new();
+        let mut steel = Vec::new();
+        let mut terrain = Vec::new();
+        for y in bounds.y_min..=bounds.y_max {
+            for x in bounds.x_min..=bounds.x_max {
+                match self.executors[(x, y)] {
+                    MapPlace::Block(hp) => blocks.push(((x, y), hp)),
+                    MapPlace::Steel => steel.push((x, y)),
+                    _ => {}
+                }
+                match self.executors.terrain(x, y) {
+                    Some(Terrain::Ground) | None => {}
+                    Some(kind) => terrain.push(((x, y), kind)),
+                }
+            }
+        }
+        Level {
+            bounds,
+            difficulty: self.difficulty,
+            heading: self.heading,
+            blocks,
+            steel,
+            terrain,
+            base: self.base(),
+            spawns: self.spawns.clone(),
+        }
+    }
+
+    // 用同样的地图设置和新的种子开一局：关卡地图原样重建，随机地图重新生成
+    pub fn replay(&self, seed: u64) -> Self {
+        let map = match &self.level {
+            Some(level) => Executors::from_level(level.clone(), seed),
+            None => {
+                let mut map = Executors::with_generator(
+                    self.bounds(),
+                    seed,
+                    self.spawns.clone(),
+                    self.generator,
+                );
+                map.set_difficulty(self.difficulty);
+                map
+            }
+        };
+        // 开局后用 with_base 放置的基地重新放置
+        if self.base.is_some() && map.base.is_none() {
+            map.with_base()
+        } else {
+            map
+        }
+    }
+
+    // 在玩家出生点旁放置基地并围上一圈砖墙，基地和墙都不会把出生点隔开
+    pub fn with_base(mut self) -> Self {
+        let bounds = self.bounds();
+        let mut keys = spawn_cells(bounds, &self.spawns);
+        keys.push((self.player_x, self.player_y));
+        let mut blocked = self.obstacles();
+        let accept = |map: &Self, x: i32, y: i32| {
+            map.executors.passable(x, y) && keeps_connected(bounds, &blocked, &keys, &[(x, y)])
+        };
+        // 优先放在玩家左边隔一格的地方
+        let Some((x, y)) = self.cell_near(self.player_x - 2, self.player_y, accept) else {
+            return self;
+        };
+        self.executors[(x, y)] = MapPlace::Base;
+        self.base = Some((x, y));
+        blocked.insert((x, y));
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let wall = (x + dx, y + dy);
+                if bounds.contains(wall.0, wall.1)
+                    && self.executors.passable(wall.0, wall.1)
+                    && keeps_connected(bounds, &blocked, &keys, &[wall])
+                {
+                    self.executors[wall] = MapPlace::Block(BLOCK_HP);
+                    self.block_durability.insert(wall, BLOCK_HP);
+                    blocked.insert(wall);
+                }
+            }
+        }
+        self
+    }
+
+    // 还没被摧毁的基地的位置
+    pub fn base(&self) -> Option<(i32, i32)> {
+        self.base
+            .filter(|(x, y)| self.executors[(*x, *y)] == MapPlace::Base)
+    }
+
+    pub fn is_base_destroyed(&self) -> bool {
+        self.base.is_some() && self.base().is_none()
+    }
+
+    // 基地被击中，游戏结束
+    fn destroy_base(&mut self, x: i32, y: i32) {
+        self.executors[(x, y)] = MapPlace::Place;
+        self.is_lose = true;
+    }
+
+    fn build(executors: Board, rng: StdRng, seed: u64, spawns: Spawns, heading: Heading) -> Self {
+        let bounds = executors.bounds();
+        let mut executors = executors;
+        let (player_x, player_y) = *spawns
+            .player
+            .iter()
+            .find(|(x, y)| bounds.contains(*x, *y))
+            .unwrap();
+        executors[(player_x, player_y)] = Player(Executor::with_pose(
+            Pose::new(player_x, player_y, heading).with_bounds(bounds),
+        ));
+        let mut map = Executors {
             executors,
-            player_x: X_MAX,
-            player_y: Y_MAX + Y_MAX,
-            enemy_place,
+            player_x,
+            player_y,
+            player_hp: PLAYER_HP,
+            lives: PLAYER_LIVES,
+            invulnerable: 0,
+            enemy_place: Vec::new(),
+            spawns,
+            heading,
+            spawn_cursor: 0,
+            wave: 1,
+            to_spawn: Wave::new(1).enemies.into(),
             shoot_place: Vec::new(),
             point: 0,
             is_lose: false,
+            effects: Vec::new(),
+            difficulty: Difficulty::default(),
+            level: None,
+            generator: Generator::default(),
+            base: None,
+            block_durability: HashMap::new(),
+            seed,
+            rng,
+        };
+        for y in bounds.y_min..=bounds.y_max {
+            for x in bounds.x_min..=bounds.x_max {
+                if let MapPlace::Block(hp) = map.executors[(x, y)] {
+                    map.block_durability.insert((x, y), hp);
+                }
+            }
+        }
+        // 开局时出生点上的方块被清除，其余规则与刷新时相同
+        for i in 0..map.spawns.enemies.len() {
+            let SpawnPoint { x, y, behavior } = map.spawns.enemies[i];
+            if let Some(MapPlace::Block(_)) = map.executors.get(x, y) {
+                map.executors[(x, y)] = MapPlace::Place;
+            }
+            if !map.is_safe_spawn(x, y) {
+                continue;
+            }
+            if let Some(kind) = map.next_enemy(behavior) {
+                map.spawn_enemy_at(x, y, kind);
+            }
+        }
+        map
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.executors.bounds()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.difficulty = difficulty;
+        // 已在场的敌人按新难度重新生成行为
+        for enemy in &mut self.enemy_place {
+            enemy.behavior = enemy.kind.build(enemy.post, difficulty);
+        }
+    }
+
+    pub fn player_hp(&self) -> u32 {
+        self.player_hp
+    }
+
+    pub fn lives(&self) -> u32 {
+        self.lives
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable > 0
+    }
+
+    // 从本局的随机数发生器取出下一局的种子，使重开也可复现
+    pub fn next_seed(&mut self) -> u64 {
+        self.rng.gen()
+    }
+
+    pub fn spawns(&self) -> &Spawns {
+        &self.spawns
+    }
+
+    // (x, y) 上砖块剩余的耐久度和出现时的耐久度
+    pub fn block_durability(&self, x: i32, y: i32) -> Option<(u32, u32)> {
+        let MapPlace::Block(hp) = self.executors.get(x, y)? else {
+            return None;
+        };
+        let full = self.block_durability.get(&(x, y)).copied().unwrap_or(hp);
+        Some((hp, full.max(hp)))
+    }
+
+    // 敌人能否出生在 (x, y)：必须是坦克能进入的空地，不能紧挨玩家，也不能在玩家正前方的火力线上
+    pub fn is_safe_spawn(&self, x: i32, y: i32) -> bool {
+        if !self.executors.passable(x, y) {
+            return false;
+        }
+        let (px, py) = (self.player_x, self.player_y);
+        if (x - px).abs() + (y - py).abs() <= SPAWN_SAFE_DISTANCE {
+            return false;
+        }
+        match self.executors.get(px, py) {
+            Some(Player(player)) => player.query().is_none_or(|(_, _, heading)| {
+                ai::line_of_sight(&self.executors, (px, py), (x, y)) != Some(heading)
+            }),
+            _ => true,
+        }
+    }
+
+    // 处理一条定时事件
+    pub fn handle(&mut self, msg: AppMessage) {
+        match msg {
+            AppMessage::SpawnEnemy => self.spawn(),
+            AppMessage::MoveEnemies => self.enemy_move(),
+            AppMessage::Shoot => self.shoot(),
+            AppMessage::MoveShoot => self.shoot_move(),
+            AppMessage::SpawnBlock => self.spawn_block(),
+            AppMessage::NextWave => self.next_wave(),
+            AppMessage::SpawnPickup => self.spawn_pickup(),
         }
     }
 
+    // 每个安全的出生点各刷新一个敌人，起点轮换；所有出生点都不安全时改在离轮到的出生点最近的安全空地刷新
     pub fn spawn(&mut self) {
-        if let MapPlace::Place =
-            self.executors[(-Y_MAX + Y_MAX) as usize][(X_MAX - 2 + X_MAX) as usize]
-        {
-            self.executors[(-Y_MAX + Y_MAX) as usize][(X_MAX - 2 + X_MAX) as usize] =
-                MapPlace::Enemy(Executor::with_pose(Pose::new(X_MAX - 2, -Y_MAX, 'S')));
-            self.enemy_place.push((X_MAX - 2, -Y_MAX));
+        let count = self.spawns.enemies.len();
+        if count == 0 {
+            return;
         }
-        if let MapPlace::Place =
-            self.executors[(-Y_MAX + Y_MAX) as usize][(-X_MAX + 2 + X_MAX) as usize]
-        {
-            self.executors[(-Y_MAX + Y_MAX) as usize][(-X_MAX + 2 + X_MAX) as usize] =
-                MapPlace::Enemy(Executor::with_pose(Pose::new(-X_MAX + 2, -Y_MAX, 'S')));
-            self.enemy_place.push((-X_MAX + 2, -Y_MAX));
+        let start = self.spawn_cursor % count;
+        self.spawn_cursor = (start + 1) % count;
+        let mut spawned = false;
+        for i in (start..count).chain(0..start) {
+            let SpawnPoint { x, y, behavior } = self.spawns.enemies[i];
+            if self.is_safe_spawn(x, y) {
+                if let Some(kind) = self.next_enemy(behavior) {
+                    self.spawn_enemy_at(x, y, kind);
+                    spawned = true;
+                }
+            }
+        }
+        if spawned {
+            return;
+        }
+        let SpawnPoint { x, y, behavior } = self.spawns.enemies[start];
+        if let Some((x, y)) = self.cell_near(x, y, |map, x, y| map.is_safe_spawn(x, y)) {
+            if let Some(kind) = self.next_enemy(behavior) {
+                self.spawn_enemy_at(x, y, kind);
+            }
+        }
+    }
+
+    // 从本波取出下一个敌人，出生点指定了行为时以出生点为准；本波已全部出场时返回 None
+    fn next_enemy(&mut self, fixed: Option<BehaviorKind>) -> Option<BehaviorKind> {
+        let kind = self.to_spawn.pop_front()?;
+        Some(fixed.unwrap_or(kind))
+    }
+
+    pub fn wave(&self) -> u32 {
+        self.wave
+    }
+
+    // 本波还剩的敌人，包括在场的和尚未出场的
+    pub fn enemies_remaining(&self) -> usize {
+        self.enemy_place.len() + self.to_spawn.len()
+    }
+
+    pub fn is_wave_cleared(&self) -> bool {
+        self.enemies_remaining() == 0
+    }
+
+    // 结算清完的一波并准备下一波
+    pub fn next_wave(&mut self) {
+        if self.is_wave_cleared() {
+            self.point += WAVE_BONUS * self.wave as i32;
         }
+        self.wave += 1;
+        self.to_spawn = Wave::new(self.wave).enemies.into();
+    }
+
+    fn spawn_enemy_at(&mut self, x: i32, y: i32, behavior: BehaviorKind) {
+        let bounds = self.bounds();
+        self.executors[(x, y)] = MapPlace::Enemy(Executor::with_pose(
+            Pose::new(x, y, Heading::S).with_bounds(bounds),
+        ));
+        let reload = self.rng.gen_range(ENEMY_RELOAD);
+        self.enemy_place.push(EnemyState {
+            x,
+            y,
+            hp: ENEMY_HP,
+            reload,
+            kind: behavior,
+            post: (x, y),
+            behavior: behavior.build((x, y), self.difficulty),
+        });
     }
 
+    // 随机在普通地面上刷新砖块，会把玩家和出生点隔开的砖块不会出现
     pub fn spawn_block(&mut self) {
-        for _ in 0..3 {
-            let mut rng = rand::thread_rng();
-            let block_x = rng.gen_range(0..13);
-            let block_y = rng.gen_range(0..11);
-            if (block_x, block_y) != (self.player_x, self.player_y)
-                && !self
-                    .enemy_place
-                    .contains(&(block_x - X_MAX, block_y - Y_MAX))
-                && !self
-                    .shoot_place
-                    .contains(&(block_x - X_MAX, block_y - Y_MAX))
+        let bounds = self.bounds();
+        let mut keys = spawn_cells(bounds, &self.spawns);
+        keys.push((self.player_x, self.player_y));
+        let mut blocks = self.obstacles();
+        for _ in 0..scaled(3, bounds) {
+            let block_x = self.rng.gen_range(bounds.x_min..=bounds.x_max);
+            let block_y = self.rng.gen_range(bounds.y_min..=bounds.y_max);
+            // 只落在空着的普通地面上，不会压住单位、子弹和道具
+            if self.executors.terrain(block_x, block_y) == Some(Terrain::Ground)
+                && self.executors[(block_x, block_y)] == MapPlace::Place
+                && keeps_connected(bounds, &blocks, &keys, &[(block_x, block_y)])
             {
-                self.executors[block_y as usize][block_x as usize] = MapPlace::Block;
+                self.executors[(block_x, block_y)] = MapPlace::Block(BLOCK_HP);
+                self.block_durability.insert((block_x, block_y), BLOCK_HP);
+                blocks.insert((block_x, block_y));
+            }
+        }
+    }
+
+    pub fn generator(&self) -> Generator {
+        self.generator
+    }
+
+    // 在随机的空地上刷新一个道具，场上道具已达上限时不刷新
+    pub fn spawn_pickup(&mut self) {
+        let pickups = self
+            .executors
+            .rows()
+            .flatten()
+            .filter(|cell| matches!(cell, MapPlace::Pickup(_)))
+            .count();
+        if pickups >= MAX_PICKUPS {
+            return;
+        }
+        let bounds = self.bounds();
+        let x = self.rng.gen_range(bounds.x_min..=bounds.x_max);
+        let y = self.rng.gen_range(bounds.y_min..=bounds.y_max);
+        if self.executors.passable(x, y) && self.keeps_spawns_connected(x, y) {
+            self.executors[(x, y)] = MapPlace::Pickup(self.random_power_up());
+        }
+    }
+
+    // 挡住坦克的格子
+    fn obstacles(&self) -> HashSet<(i32, i32)> {
+        let bounds = self.bounds();
+        let mut blocked = HashSet::new();
+        for y in bounds.y_min..=bounds.y_max {
+            for x in bounds.x_min..=bounds.x_max {
+                if self.executors.is_obstacle(x, y) {
+                    blocked.insert((x, y));
+                }
+            }
+        }
+        blocked
+    }
+
+    // 在 (x, y) 放下道具后玩家和所有出生点是否仍然连通
+    fn keeps_spawns_connected(&self, x: i32, y: i32) -> bool {
+        let bounds = self.bounds();
+        let mut keys = spawn_cells(bounds, &self.spawns);
+        keys.push((self.player_x, self.player_y));
+        keeps_connected(bounds, &self.obstacles(), &keys, &[(x, y)])
+    }
+
+    fn random_power_up(&mut self) -> PowerUp {
+        PowerUp::ALL[self.rng.gen_range(0..PowerUp::ALL.len())]
+    }
+
+    // 生效中的道具及其剩余时间
+    pub fn effects(&self) -> &[(PowerUp, Duration)] {
+        &self.effects
+    }
+
+    pub fn has_effect(&self, power_up: PowerUp) -> bool {
+        self.effects.iter().any(|(p, _)| *p == power_up)
+    }
+
+    // 游戏时间流逝 dt，到时的道具效果消失
+    pub fn elapse(&mut self, dt: Duration) {
+        for (_, left) in &mut self.effects {
+            *left = left.saturating_sub(dt);
+        }
+        self.effects.retain(|(_, left)| !left.is_zero());
+    }
+
+    // 拾取道具：持续效果重新计时，其余立即生效
+    fn collect(&mut self, power_up: PowerUp) {
+        match power_up {
+            PowerUp::ExtraLife => self.lives += 1,
+            PowerUp::Bomb => {
+                for enemy in std::mem::take(&mut self.enemy_place) {
+                    self.executors[(enemy.x, enemy.y)] = MapPlace::Place;
+                    self.point += 1;
+                }
+            }
+            _ => {
+                if let Some(duration) = power_up.duration() {
+                    self.effects.retain(|(p, _)| *p != power_up);
+                    self.effects.push((power_up, duration));
+                }
             }
         }
     }
 
-    pub fn player_move(&mut self, cmds: &str) {
-        if let Player(mut player) = self.executors[self.player_y as usize][self.player_x as usize] {
-            match cmds {
-                "M" => {
-                    let (x_, y_, heading) = player.query();
-                    let mut temp = Executor::with_pose(Pose::new(x_, y_, heading));
-                    temp.execute("M");
-                    let (x, y, _) = temp.query();
-                    if let MapPlace::Place =
-                        self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize]
-                    {
-                        player.execute(cmds);
-                        let (player_x, player_y, _) = player.query();
-                        self.player_x = player_x + X_MAX;
-                        self.player_y = player_y + Y_MAX;
-                        self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] = Player(player);
-                        self.executors[(y_ + Y_MAX) as usize][(x_ + X_MAX) as usize] =
-                            MapPlace::Place;
+    pub fn player_move(&mut self, cmd: Command) {
+        if let Player(mut player) = self.executors[(self.player_x, self.player_y)] {
+            match cmd {
+                Command::Move => {
+                    let (x_, y_) = (self.player_x, self.player_y);
+                    let executors = &self.executors;
+                    drive(executors, &mut player, cmd, |x, y| {
+                        executors.player_passable(x, y)
+                    });
+                    let Some((x, y, _)) = player.query() else {
+                        self.lose_life();
+                        return;
+                    };
+                    self.player_x = x;
+                    self.player_y = y;
+                    self.executors[(x_, y_)] = MapPlace::Place;
+                    if let MapPlace::Pickup(power_up) = self.executors[(x, y)] {
+                        self.collect(power_up);
                     }
+                    self.executors[(x, y)] = Player(player);
                 }
                 _ => {
-                    player.execute(cmds);
-                    self.executors[self.player_y as usize][self.player_x as usize] = Player(player);
+                    player.apply(cmd);
+                    self.executors[(self.player_x, self.player_y)] = Player(player);
                 }
             }
         }
     }
 
-    pub fn enemy_move(&mut self) {
-        let mut new_place = Vec::<(i32, i32)>::new();
-        for (enemy_x, enemy_y) in &self.enemy_place {
-            if let MapPlace::Enemy(mut enemy) =
-                self.executors[(enemy_y + Y_MAX) as usize][(enemy_x + X_MAX) as usize]
-            {
-                let mut rng = rand::thread_rng();
-                let behave = rng.gen_range(0..6);
-                match behave {
-                    0 | 3 | 4 | 5 => {
-                        let (x_, y_, heading) = enemy.query();
-                        let mut temp = Executor::with_pose(Pose::new(x_, y_, heading));
-                        temp.execute("M");
-                        let (x, y, _) = temp.query();
-                        if let MapPlace::Place =
-                            self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize]
-                        {
-                            enemy.execute("M");
-                            self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] =
-                                MapPlace::Enemy(enemy);
-                            new_place.push((x, y));
-                            self.executors[(y_ + Y_MAX) as usize][(x_ + X_MAX) as usize] =
-                                MapPlace::Place;
-                        } else {
-                            let behave = rng.gen_range(0..2);
-                            match behave {
-                                1 => enemy.execute("R"),
-                                2 => enemy.execute("L"),
-                                _ => {}
-                            }
-                            self.executors[(y_ + Y_MAX) as usize][(x_ + X_MAX) as usize] =
-                                MapPlace::Enemy(enemy);
-                            new_place.push((x_, y_));
-                        }
-                    }
-                    1 => {
-                        enemy.execute("R");
-                        self.executors[(enemy_y + Y_MAX) as usize][(enemy_x + X_MAX) as usize] =
-                            MapPlace::Enemy(enemy);
-                        new_place.push((*enemy_x, *enemy_y));
+    // 玩家被击中：无敌或有护盾时不受伤，生命值耗尽时失去一条命
+    fn hit_player(&mut self) {
+        if self.invulnerable > 0 || self.has_effect(PowerUp::Shield) {
+            return;
+        }
+        self.player_hp = self.player_hp.saturating_sub(1);
+        if self.player_hp == 0 {
+            self.lose_life();
+        } else {
+            self.invulnerable = INVULNERABLE_STEPS;
+        }
+    }
+
+    // 失去一条命并在出生点重生，命数用完时游戏结束
+    fn lose_life(&mut self) {
+        if let Player(_) = self.executors[(self.player_x, self.player_y)] {
+            self.executors[(self.player_x, self.player_y)] = MapPlace::Place;
+        }
+        self.lives = self.lives.saturating_sub(1);
+        if self.lives == 0 {
+            self.is_lose = true;
+            return;
+        }
+        let Some((x, y)) = self.player_respawn() else {
+            self.is_lose = true;
+            return;
+        };
+        let bounds = self.bounds();
+        self.executors[(x, y)] = Player(Executor::with_pose(
+            Pose::new(x, y, Heading::N).with_bounds(bounds),
+        ));
+        self.player_x = x;
+        self.player_y = y;
+        self.player_hp = PLAYER_HP;
+        self.invulnerable = INVULNERABLE_STEPS;
+    }
+
+    // 优先选择没有敌人能看到的玩家出生点，其次是空着的出生点，都不行时选第一个出生点附近的空地
+    fn player_respawn(&self) -> Option<(i32, i32)> {
+        let free = |(x, y): &(i32, i32)| self.executors.passable(*x, *y);
+        let unseen = |cell: &(i32, i32)| {
+            !self.enemy_place.iter().any(|enemy| {
+                ai::line_of_sight(&self.executors, (enemy.x, enemy.y), *cell).is_some()
+            })
+        };
+        let points = &self.spawns.player;
+        if let Some(cell) = points
+            .iter()
+            .copied()
+            .find(|cell| free(cell) && unseen(cell))
+        {
+            return Some(cell);
+        }
+        if let Some(cell) = points.iter().copied().find(free) {
+            return Some(cell);
+        }
+        let (x, y) = points[0];
+        self.cell_near(x, y, |map, x, y| map.executors.passable(x, y))
+    }
+
+    // 由近及远查找离 (x, y) 最近的满足条件的格子
+    fn cell_near(
+        &self,
+        x: i32,
+        y: i32,
+        accept: impl Fn(&Self, i32, i32) -> bool,
+    ) -> Option<(i32, i32)> {
+        let bounds = self.bounds();
+        let max_r = bounds.width().max(bounds.height()) as i32;
+        for r in 0..=max_r {
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    if dx.abs() != r && dy.abs() != r {
+                        continue;
                     }
-                    2 => {
-                        enemy.execute("L");
-                        self.executors[(enemy_y + Y_MAX) as usize][(enemy_x + X_MAX) as usize] =
-                            MapPlace::Enemy(enemy);
-                        new_place.push((*enemy_x, *enemy_y));
+                    if accept(self, x + dx, y + dy) {
+                        return Some((x + dx, y + dy));
                     }
-                    _ => {}
                 }
             }
         }
-        self.enemy_place = new_place;
+        None
+    }
+
+    // 敌人被击中，返回是否被消灭
+    fn hit_enemy(&mut self, x: i32, y: i32) -> bool {
+        let Some(i) = self
+            .enemy_place
+            .iter()
+            .position(|enemy| (enemy.x, enemy.y) == (x, y))
+        else {
+            return true;
+        };
+        self.enemy_place[i].hp = self.enemy_place[i].hp.saturating_sub(1);
+        if self.enemy_place[i].hp > 0 {
+            return false;
+        }
+        self.enemy_place.remove(i);
+        true
+    }
+
+    // 子弹击中敌人，消灭时得分，并有一定概率在原地留下道具，道具不会隔断出生点
+    fn shoot_enemy(&mut self, x: i32, y: i32) {
+        if !self.hit_enemy(x, y) {
+            return;
+        }
+        self.point += 1;
+        self.executors[(x, y)] =
+            if self.rng.gen_bool(PICKUP_DROP_CHANCE) && self.keeps_spawns_connected(x, y) {
+                MapPlace::Pickup(self.random_power_up())
+            } else {
+                MapPlace::Place
+            };
+    }
+
+    // 砖块被击中，耐久度耗尽时变为空地
+    fn hit_block(&mut self, x: i32, y: i32) {
+        if let MapPlace::Block(hp) = self.executors[(x, y)] {
+            self.executors[(x, y)] = if hp > 1 {
+                MapPlace::Block(hp - 1)
+            } else {
+                MapPlace::Place
+            };
+        }
+    }
+
+    // 每个敌人按各自的行为行动一步，冻结期间不动
+    pub fn enemy_move(&mut self) {
+        if self.has_effect(PowerUp::Freeze) {
+            return;
+        }
+        let player = (self.player_x, self.player_y);
+        let base = self.base();
+        let mut enemies = std::mem::take(&mut self.enemy_place);
+        enemies.retain_mut(|state| {
+            let (x_, y_) = (state.x, state.y);
+            let MapPlace::Enemy(mut enemy) = self.executors[(x_, y_)] else {
+                return false;
+            };
+            let Some((_, _, heading)) = enemy.query() else {
+                return false;
+            };
+            let view = EnemyView {
+                board: &self.executors,
+                position: (x_, y_),
+                heading,
+                player,
+                base,
+            };
+            let Some(cmd) = state.behavior.next_command(&view, &mut self.rng) else {
+                return true;
+            };
+            if cmd != Command::Move {
+                enemy.apply(cmd);
+                self.executors[(x_, y_)] = MapPlace::Enemy(enemy);
+                return true;
+            }
+            let executors = &self.executors;
+            drive(executors, &mut enemy, cmd, |x, y| executors.passable(x, y));
+            self.executors[(x_, y_)] = MapPlace::Place;
+            // 驶出致命边界
+            let Some((x, y, _)) = enemy.query() else {
+                return false;
+            };
+            self.executors[(x, y)] = MapPlace::Enemy(enemy);
+            (state.x, state.y) = (x, y);
+            true
+        });
+        self.enemy_place = enemies;
     }
 
+    // 每个敌人按各自的倒计时装填，装填完毕后按行为决定的方向开火；玩家开火见 player_shoot
     pub fn shoot(&mut self) {
-        let mut shoot = self.enemy_place.clone();
-        shoot.push((self.player_x - X_MAX, self.player_y - Y_MAX));
-        for (enemy_x, enemy_y) in &shoot {
-            if let MapPlace::Enemy(enemy) | Player(enemy) =
-                self.executors[(enemy_y + Y_MAX) as usize][(enemy_x + X_MAX) as usize]
-            {
-                let (x_, y_, heading) = enemy.query();
-                let mut temp = Executor::with_pose(Pose::new(x_, y_, heading));
-                temp.execute("M");
-                let (x, y, _) = temp.query();
-                if let MapPlace::Place = self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize]
-                {
-                    let shoot = Executor::with_pose(Pose::new(x, y, heading));
-                    shoot.pose.unwrap().is_shoot = true;
-                    self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] =
-                        MapPlace::Shoot(shoot);
-                    self.shoot_place.push((x, y));
+        if self.has_effect(PowerUp::Freeze) {
+            return;
+        }
+        for i in 0..self.enemy_place.len() {
+            let EnemyState { x, y, reload, .. } = self.enemy_place[i];
+            let MapPlace::Enemy(mut enemy) = self.executors[(x, y)] else {
+                continue;
+            };
+            if reload > 1 {
+                self.enemy_place[i].reload -= 1;
+            }
+            let Some((_, _, heading)) = enemy.query() else {
+                continue;
+            };
+            let view = EnemyView {
+                board: &self.executors,
+                position: (x, y),
+                heading,
+                player: (self.player_x, self.player_y),
+                base: self.base(),
+            };
+            let Some(heading) = self.enemy_place[i].behavior.aim(&view) else {
+                continue;
+            };
+            turn_towards(&mut enemy, heading);
+            self.executors[(x, y)] = MapPlace::Enemy(enemy);
+            if reload > 1 {
+                continue;
+            }
+            self.fire_from(x, y);
+            self.enemy_place[i].reload = self.rng.gen_range(ENEMY_RELOAD);
+        }
+    }
+
+    // 只有玩家开火
+    pub fn player_shoot(&mut self) {
+        self.fire_from(self.player_x, self.player_y);
+    }
+
+    // 在 (x, y) 处的单位前方生成一颗子弹
+    fn fire_from(&mut self, x: i32, y: i32) {
+        let shooter = self.executors[(x, y)];
+        if let MapPlace::Enemy(enemy) | Player(enemy) = shooter {
+            let Some(pose) = enemy.pose() else {
+                return;
+            };
+            let heading = pose.heading;
+            let mut temp = Executor::with_pose(pose);
+            temp.apply(Command::Move);
+            let Some(Pose { x, y, .. }) = temp.pose() else {
+                return;
+            };
+            // 紧贴目标时直接命中
+            match (shooter, self.executors[(x, y)]) {
+                (MapPlace::Enemy(_), Player(_)) => {
+                    self.hit_player();
+                    return;
+                }
+                (Player(_), MapPlace::Enemy(_)) => {
+                    self.shoot_enemy(x, y);
+                    return;
+                }
+                (_, MapPlace::Block(_)) => {
+                    self.hit_block(x, y);
+                    return;
                 }
+                (_, MapPlace::Base) => {
+                    self.destroy_base(x, y);
+                    return;
+                }
+                _ => {}
+            }
+            if let MapPlace::Place = self.executors[(x, y)] {
+                let bounds = self.bounds();
+                let shoot = Executor::with_pose(Pose::new(x, y, heading).with_bounds(bounds));
+                self.executors[(x, y)] = MapPlace::Shoot(shoot);
+                let range = match heading {
+                    Heading::E | Heading::W => bounds.width(),
+                    Heading::N | Heading::S => bounds.height(),
+                };
+                self.shoot_place.push(Shot { x, y, range });
             }
         }
     }
 
     pub fn shoot_move(&mut self) {
-        let mut new_place = Vec::<(i32, i32)>::new();
-        for (shoot_x, shoot_y) in &self.shoot_place {
-            if let MapPlace::Shoot(mut shoot) =
-                self.executors[(shoot_y + Y_MAX) as usize][(shoot_x + X_MAX) as usize]
-            {
-                let (x_, y_, heading) = shoot.query();
-                let mut temp = Executor::with_pose(Pose::new(x_, y_, heading));
-                temp.execute("M");
-                let (x, y, _) = temp.query();
-                match self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] {
+        self.invulnerable = self.invulnerable.saturating_sub(1);
+        let mut new_place = Vec::<Shot>::new();
+        for shot in std::mem::take(&mut self.shoot_place) {
+            if let MapPlace::Shoot(mut shoot) = self.executors[(shot.x, shot.y)] {
+                let (x_, y_) = (shot.x, shot.y);
+                let mut temp = shoot;
+                temp.apply(Command::Move);
+                let Some(Pose { x, y, .. }) = temp.pose() else {
+                    // 飞出致命边界
+                    self.executors[(x_, y_)] = MapPlace::Place;
+                    continue;
+                };
+                if shot.range <= 1 {
+                    self.executors[(x_, y_)] = MapPlace::Place;
+                    continue;
+                }
+                match self.executors[(x, y)] {
                     MapPlace::Place => {
-                        shoot.execute("M");
-                        self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] =
-                            MapPlace::Shoot(shoot);
-                        new_place.push((x, y));
-                        self.executors[(y_ + Y_MAX) as usize][(x_ + X_MAX) as usize] =
-                            MapPlace::Place;
+                        shoot.apply(Command::Move);
+                        self.executors[(x, y)] = MapPlace::Shoot(shoot);
+                        new_place.push(Shot {
+                            x,
+                            y,
+                            range: shot.range - 1,
+                        });
+                        self.executors[(x_, y_)] = MapPlace::Place;
                     }
                     MapPlace::Shoot(_shoot) => {
-                        self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] =
-                            MapPlace::Place;
-                        self.executors[(y_ + Y_MAX) as usize][(x_ + X_MAX) as usize] =
-                            MapPlace::Place;
+                        self.executors[(x, y)] = MapPlace::Place;
+                        self.executors[(x_, y_)] = MapPlace::Place;
                     }
                     MapPlace::Enemy(_enemy) => {
-                        self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] =
-                            MapPlace::Place;
-                        self.executors[(y_ + Y_MAX) as usize][(x_ + X_MAX) as usize] =
-                            MapPlace::Place;
-                        self.point += 1;
+                        self.executors[(x_, y_)] = MapPlace::Place;
+                        self.shoot_enemy(x, y);
                     }
                     Player(_player) => {
-                        self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] =
-                            MapPlace::Place;
-                        self.executors[(y_ + Y_MAX) as usize][(x_ + X_MAX) as usize] =
-                            MapPlace::Place;
-                        self.is_lose = true;
+                        self.executors[(x_, y_)] = MapPlace::Place;
+                        self.hit_player();
+                    }
+                    MapPlace::Block(_) => {
+                        self.executors[(x_, y_)] = MapPlace::Place;
+                        self.hit_block(x, y);
                     }
-                    MapPlace::Block => {
-                        self.executors[(y + Y_MAX) as usize][(x + X_MAX) as usize] =
-                            MapPlace::Place;
-                        self.executors[(y_ + Y_MAX) as usize][(x_ + X_MAX) as usize] =
-                            MapPlace::Place;
+                    MapPlace::Steel | MapPlace::Pickup(_) => {
+                        self.executors[(x_, y_)] = MapPlace::Place;
+                    }
+                    MapPlace::Base => {
+                        self.executors[(x_, y_)] = MapPlace::Place;
+                        self.destroy_base(x, y);
                     }
                 }
             }
@@ -393,3 +1317,46 @@ impl Executors {
         self.shoot_place = new_place;
     }
 }
+
+// 坦克移动：只能进入 passable 的格子，停在冰面上时沿原方向再滑行一格
+fn drive(board: &Board, tank: &mut Executor, cmd: Command, passable: impl Fn(i32, i32) -> bool) {
+    let from = tank.pose().map(|pose| (pose.x, pose.y));
+    tank.apply_with(cmd, &passable);
+    let Some(pose) = tank.pose() else {
+        return;
+    };
+    if Some((pose.x, pose.y)) == from || board.terrain(pose.x, pose.y) != Some(Terrain::Ice) {
+        return;
+    }
+    // 加速模式下也只多滑一格
+    let fast = tank.fast;
+    tank.fast = false;
+    tank.apply_with(Command::Move, &passable);
+    tank.fast = fast;
+}
+
+// 用一条转向指令转到指定朝向
+fn turn_towards(executor: &mut Executor, heading: Heading) {
+    let Some((_, _, current)) = executor.query() else {
+        return;
+    };
+    if let Some(cmd) = ai::turn_command(current, heading) {
+        executor.apply(cmd);
+    }
+}
+
+// 地图内的所有出生点，生成地图和刷新方块时必须保持连通
+fn spawn_cells(bounds: Bounds, spawns: &Spawns) -> Vec<(i32, i32)> {
+    spawns
+        .player
+        .iter()
+        .copied()
+        .chain(spawns.enemies.iter().map(|p| (p.x, p.y)))
+        .filter(|(x, y)| bounds.contains(*x, *y))
+        .collect()
+}
+
+// 按地图面积缩放数量，base 对应默认 13x11 地图
+fn scaled(base: usize, bounds: Bounds) -> usize {
+    (base * bounds.area() / Bounds::default().area()).max(1)
+}
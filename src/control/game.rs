@@ -0,0 +1,312 @@
+use crate::control::executor::{Command, Executors};
+use crate::control::play::{AppMessage, Clock, Scheduler};
+use crate::control::powerup::{PowerUp, RAPID_FIRE_DIVISOR};
+use crate::control::wave::Wave;
+use std::time::Duration;
+
+// 每次 tick 推进的游戏时间
+pub const TICK: Duration = Duration::from_millis(100);
+
+// 周期事件：首次在 delay 时触发，之后每隔 period 触发一次
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cadence {
+    pub delay: Duration,
+    pub period: Duration,
+}
+
+impl Cadence {
+    pub const fn new(delay: Duration, period: Duration) -> Self {
+        Cadence { delay, period }
+    }
+}
+
+// 各类游戏事件的节奏
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cadences {
+    pub spawn_enemy: Cadence,
+    pub move_enemies: Cadence,
+    // 敌人开火倒计时的步进节奏，每个敌人隔若干步开火一次
+    pub shoot: Cadence,
+    pub move_shoot: Cadence,
+    pub spawn_block: Cadence,
+    pub spawn_pickup: Cadence,
+    // 玩家两次开火之间的最短间隔
+    pub fire_cooldown: Duration,
+    // 清完一波后到下一波开始的休息时间
+    pub breather: Duration,
+}
+
+impl Cadences {
+    pub fn events(&self) -> [(&'static str, AppMessage, Cadence); 6] {
+        [
+            ("spawn_enemy", AppMessage::SpawnEnemy, self.spawn_enemy),
+            ("move_enemies", AppMessage::MoveEnemies, self.move_enemies),
+            ("shoot", AppMessage::Shoot, self.shoot),
+            ("move_shoot", AppMessage::MoveShoot, self.move_shoot),
+            ("spawn_block", AppMessage::SpawnBlock, self.spawn_block),
+            ("spawn_pickup", AppMessage::SpawnPickup, self.spawn_pickup),
+        ]
+    }
+
+    // 按这些节奏注册好所有事件的调度器
+    pub fn scheduler(&self) -> Scheduler<AppMessage> {
+        let mut scheduler = Scheduler::new();
+        for (name, msg, cadence) in self.events() {
+            scheduler.every(name, msg, cadence.delay, cadence.period);
+        }
+        scheduler
+    }
+}
+
+impl Default for Cadences {
+    fn default() -> Self {
+        Cadences {
+            spawn_enemy: Cadence::new(Duration::ZERO, Duration::from_secs(10)),
+            move_enemies: Cadence::new(Duration::from_secs(3), Duration::from_secs(1)),
+            shoot: Cadence::new(Duration::from_secs(3), Duration::from_millis(500)),
+            move_shoot: Cadence::new(Duration::from_secs(5), Duration::from_secs(1)),
+            spawn_block: Cadence::new(Duration::from_secs(20), Duration::from_secs(10)),
+            spawn_pickup: Cadence::new(Duration::from_secs(15), Duration::from_secs(15)),
+            fire_cooldown: Duration::from_millis(500),
+            breather: Duration::from_secs(5),
+        }
+    }
+}
+
+// 一局游戏所处的阶段
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameState {
+    MainMenu,
+    Running,
+    Paused,
+    GameOver,
+}
+
+// 不依赖 GUI 和线程的游戏核心，由调用方逐 tick 推进
+pub struct Game {
+    map: Executors,
+    cadences: Cadences,
+    scheduler: Scheduler<AppMessage>,
+    state: GameState,
+    ticks: u64,
+    last_fire: Option<Duration>,
+    // 已清完一波，正在等待下一波
+    between_waves: bool,
+}
+
+impl Game {
+    // 新游戏停在主菜单，调用 start() 后开始
+    pub fn new(map: Executors) -> Self {
+        Game::with_cadences(map, Cadences::default())
+    }
+
+    pub fn with_cadences(map: Executors, cadences: Cadences) -> Self {
+        // 主菜单下调度器保持暂停，开始后才计时
+        let mut scheduler = cadences.scheduler();
+        scheduler.pause();
+        Game {
+            map,
+            cadences,
+            scheduler,
+            state: GameState::MainMenu,
+            ticks: 0,
+            last_fire: None,
+            between_waves: false,
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    pub fn start(&mut self) {
+        if self.state == GameState::MainMenu {
+            self.scheduler.resume();
+            self.state = GameState::Running;
+        }
+    }
+
+    // 暂停时敌人、子弹和方块刷新一起冻结
+    pub fn pause(&mut self) {
+        if self.state == GameState::Running {
+            self.scheduler.pause();
+            self.state = GameState::Paused;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.state == GameState::Paused {
+            self.scheduler.resume();
+            self.state = GameState::Running;
+        }
+    }
+
+    // 用同样大小的地图重新开一局，种子取自上一局的随机数发生器
+    pub fn restart(&mut self) {
+        let seed = self.map.next_seed();
+        self.map = self.map.replay(seed);
+        self.scheduler = self.cadences.scheduler();
+        self.ticks = 0;
+        self.last_fire = None;
+        self.between_waves = false;
+        self.state = GameState::Running;
+    }
+
+    // 推进一个 TICK，按触发时间顺序处理其间到期的所有事件并返回；只在运行状态下生效
+    pub fn tick(&mut self) -> Vec<AppMessage> {
+        if self.state != GameState::Running {
+            return Vec::new();
+        }
+        self.ticks += 1;
+        self.advance(TICK)
+    }
+
+    // 推进任意长度的游戏时间
+    pub fn advance(&mut self, dt: Duration) -> Vec<AppMessage> {
+        let start = self.scheduler.now();
+        let due = self.scheduler.advance(dt);
+        self.run(start, due)
+    }
+
+    // 按时钟读数推进，暂停期间流逝的时间不计入
+    pub fn poll(&mut self, clock: &impl Clock) -> Vec<AppMessage> {
+        let start = self.scheduler.now();
+        let due = self.scheduler.poll(clock);
+        self.run(start, due)
+    }
+
+    // 距下一个事件到期的时间，GUI 据此安排重绘
+    pub fn until_next(&self) -> Option<Duration> {
+        if self.state == GameState::Running {
+            self.scheduler.until_next()
+        } else {
+            None
+        }
+    }
+
+    // 先让道具效果按从 start 起流逝的游戏时间计时，再处理到期的事件
+    fn run(&mut self, start: Duration, due: Vec<AppMessage>) -> Vec<AppMessage> {
+        self.map.elapse(self.scheduler.now().saturating_sub(start));
+        let mut handled = Vec::new();
+        for msg in due {
+            if !self.handle(msg) {
+                break;
+            }
+            handled.push(msg);
+        }
+        handled
+    }
+
+    // 处理一条由外部调度的事件，返回是否被处理
+    pub fn handle(&mut self, msg: AppMessage) -> bool {
+        if self.state != GameState::Running {
+            return false;
+        }
+        self.map.handle(msg);
+        if msg == AppMessage::NextWave {
+            self.start_wave();
+        }
+        self.check_wave();
+        self.check_over();
+        true
+    }
+
+    // 玩家输入，只在运行状态下生效
+    pub fn apply_input(&mut self, cmd: Command) {
+        if self.state == GameState::Running {
+            self.map.player_move(cmd);
+            // 拾取炸弹可能清空本波敌人
+            self.check_wave();
+            self.check_over();
+        }
+    }
+
+    // 玩家开火，只在运行状态下且冷却结束后生效，返回是否开火
+    pub fn fire(&mut self) -> bool {
+        if self.state != GameState::Running || self.fire_cooldown_left() > Duration::ZERO {
+            return false;
+        }
+        self.last_fire = Some(self.scheduler.now());
+        self.map.player_shoot();
+        self.check_wave();
+        //贴着基地开火会立即输掉
+        self.check_over();
+        true
+    }
+
+    // 距离下次可以开火还需的游戏时间，连射期间冷却缩短
+    pub fn fire_cooldown_left(&self) -> Duration {
+        let mut cooldown = self.cadences.fire_cooldown;
+        if self.map.has_effect(PowerUp::RapidFire) {
+            cooldown /= RAPID_FIRE_DIVISOR;
+        }
+        match self.last_fire {
+            Some(last) => (last + cooldown).saturating_sub(self.scheduler.now()),
+            None => Duration::ZERO,
+        }
+    }
+
+    // 一波敌人全部消灭后停止刷新，休息 breather 后开始下一波
+    fn check_wave(&mut self) {
+        if self.between_waves || !self.map.is_wave_cleared() {
+            return;
+        }
+        self.between_waves = true;
+        self.scheduler.cancel("spawn_enemy");
+        let breather = self.cadences.breather;
+        self.scheduler.every(
+            "next_wave",
+            AppMessage::NextWave,
+            breather,
+            breather.max(TICK),
+        );
+    }
+
+    // 按新一波的刷新间隔立即开始刷新敌人
+    fn start_wave(&mut self) {
+        self.between_waves = false;
+        self.scheduler.cancel("next_wave");
+        let period = Wave::new(self.map.wave()).spawn_period(self.cadences.spawn_enemy.period);
+        self.scheduler.every(
+            "spawn_enemy",
+            AppMessage::SpawnEnemy,
+            Duration::ZERO,
+            period,
+        );
+    }
+
+    fn check_over(&mut self) {
+        if self.map.is_lose {
+            self.scheduler.shutdown();
+            self.state = GameState::GameOver;
+        }
+    }
+
+    pub fn map(&self) -> &Executors {
+        &self.map
+    }
+
+    pub fn cadences(&self) -> &Cadences {
+        &self.cadences
+    }
+
+    pub fn point(&self) -> i32 {
+        self.map.point
+    }
+
+    pub fn wave(&self) -> u32 {
+        self.map.wave()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.state == GameState::GameOver
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.scheduler.now()
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+}
@@ -0,0 +1,248 @@
+use crate::control::ai;
+use crate::control::board::{BoundaryPolicy, Bounds};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashSet;
+
+// 地图的生成风格
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MapStyle {
+    // 随机散布的方块
+    #[default]
+    Scatter,
+    // 左右对称
+    Symmetric,
+    // 迷宫，再随机打通一部分墙
+    Maze,
+}
+
+impl MapStyle {
+    pub const ALL: [MapStyle; 3] = [MapStyle::Scatter, MapStyle::Symmetric, MapStyle::Maze];
+
+    // 各风格默认的方块密度，Scatter 对应默认 13x11 地图上 10 个方块
+    pub fn default_density(self) -> f64 {
+        match self {
+            MapStyle::Scatter => 10.0 / 143.0,
+            MapStyle::Symmetric => 0.12,
+            MapStyle::Maze => 0.35,
+        }
+    }
+}
+
+// 地图生成器，density 为方块占地图面积的比例
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Generator {
+    pub style: MapStyle,
+    pub density: f64,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator::new(MapStyle::default())
+    }
+}
+
+impl Generator {
+    pub fn new(style: MapStyle) -> Self {
+        Generator {
+            style,
+            density: style.default_density(),
+        }
+    }
+
+    pub fn with_density(self, density: f64) -> Self {
+        Generator {
+            density: density.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    // 生成方块的位置，保证 keys 中的格子（出生点等）空着且互相连通
+    pub fn generate(
+        &self,
+        bounds: Bounds,
+        keys: &[(i32, i32)],
+        rng: &mut StdRng,
+    ) -> Vec<(i32, i32)> {
+        // 加上一个很小的量，避免浮点误差把整数个方块算少一个
+        let target = ((self.density * bounds.area() as f64 + 1e-9) as usize).max(1);
+        let blocks = match self.style {
+            MapStyle::Scatter => scatter(bounds, keys, target, rng, false),
+            MapStyle::Symmetric => scatter(bounds, keys, target, rng, true),
+            MapStyle::Maze => maze(bounds, keys, target, rng),
+        };
+        // 最后再做一次连通检查，不合格的布局整个丢弃
+        if keeps_connected(bounds, &blocks, keys, &[]) {
+            let mut blocks: Vec<(i32, i32)> = blocks.into_iter().collect();
+            blocks.sort();
+            blocks
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+// 加上 extra 这些方块后，keys 中的格子是否仍然互相连通
+pub fn keeps_connected(
+    bounds: Bounds,
+    blocks: &HashSet<(i32, i32)>,
+    keys: &[(i32, i32)],
+    extra: &[(i32, i32)],
+) -> bool {
+    if extra.iter().any(|cell| keys.contains(cell)) {
+        return false;
+    }
+    let is_open = |cell: (i32, i32)| !blocks.contains(&cell) && !extra.contains(&cell);
+    // 每个新方块周围的空地在局部仍然相连时不会隔断任何格子，可以省去整张地图的搜索
+    let locally_safe = !extra.is_empty()
+        && bounds.width() >= 3
+        && bounds.height() >= 3
+        && extra.iter().enumerate().all(|(i, cell)| {
+            ring_connected(bounds, *cell, |c| {
+                !blocks.contains(&c) && !extra[..i].contains(&c)
+            })
+        });
+    locally_safe || ai::connected(bounds, keys, is_open)
+}
+
+// (x, y) 周围一圈八个格子中，上下左右的空地能否只经过这一圈互相到达
+fn ring_connected(
+    bounds: Bounds,
+    (x, y): (i32, i32),
+    is_open: impl Fn((i32, i32)) -> bool,
+) -> bool {
+    const RING: [(i32, i32); 8] = [
+        (0, -1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+        (0, 1),
+        (-1, 1),
+        (-1, 0),
+        (-1, -1),
+    ];
+    let open: Vec<bool> = RING
+        .iter()
+        .map(|(dx, dy)| {
+            let (nx, ny) = (x + dx, y + dy);
+            let cell = if bounds.contains(nx, ny) {
+                Some((nx, ny))
+            } else if bounds.policy == BoundaryPolicy::Wrap {
+                Some(bounds.wrap(nx, ny))
+            } else {
+                None
+            };
+            cell.is_some_and(|cell| cell != (x, y) && is_open(cell))
+        })
+        .collect();
+    // 数一圈中含有上下左右格子的连续空地段
+    let mut runs = 0;
+    for start in (0..8).step_by(2) {
+        if !open[start] {
+            continue;
+        }
+        // 只从一段的第一个正方向格子开始计数
+        let mut i = (start + 7) % 8;
+        let mut first = true;
+        while open[i] && i != start {
+            if i % 2 == 0 {
+                first = false;
+                break;
+            }
+            i = (i + 7) % 8;
+        }
+        if first || i == start {
+            runs += 1;
+        }
+    }
+    runs <= 1
+}
+
+// 逐个随机放置方块，会隔断 keys 的方块被放弃；symmetric 时成对放置左右对称的方块
+fn scatter(
+    bounds: Bounds,
+    keys: &[(i32, i32)],
+    target: usize,
+    rng: &mut StdRng,
+    symmetric: bool,
+) -> HashSet<(i32, i32)> {
+    let mut blocks = HashSet::new();
+    let attempts = if symmetric {
+        target.div_ceil(2)
+    } else {
+        target
+    };
+    for _ in 0..attempts {
+        let x = rng.gen_range(bounds.x_min..=bounds.x_max);
+        let y = rng.gen_range(bounds.y_min..=bounds.y_max);
+        let mut cells = vec![(x, y)];
+        if symmetric {
+            let mirror = (bounds.x_min + bounds.x_max - x, y);
+            if mirror != (x, y) {
+                cells.push(mirror);
+            }
+        }
+        if keeps_connected(bounds, &blocks, keys, &cells) {
+            blocks.extend(cells);
+        }
+    }
+    blocks
+}
+
+// 以偶数偏移的格子为通道用随机深度优先生成迷宫，打通 keys，再随机拆墙直到密度降到 target
+fn maze(
+    bounds: Bounds,
+    keys: &[(i32, i32)],
+    target: usize,
+    rng: &mut StdRng,
+) -> HashSet<(i32, i32)> {
+    let cell = |ox: i32, oy: i32| (bounds.x_min + ox, bounds.y_min + oy);
+    let (width, height) = (bounds.width() as i32, bounds.height() as i32);
+    let mut blocks = HashSet::new();
+    for oy in 0..height {
+        for ox in 0..width {
+            blocks.insert(cell(ox, oy));
+        }
+    }
+    let mut stack = vec![(0, 0)];
+    blocks.remove(&cell(0, 0));
+    while let Some(&(ox, oy)) = stack.last() {
+        let mut next: Vec<(i32, i32)> = [(0, -2), (2, 0), (0, 2), (-2, 0)]
+            .into_iter()
+            .map(|(dx, dy)| (ox + dx, oy + dy))
+            .filter(|(nx, ny)| {
+                (0..width).contains(nx)
+                    && (0..height).contains(ny)
+                    && blocks.contains(&cell(*nx, *ny))
+            })
+            .collect();
+        if next.is_empty() {
+            stack.pop();
+            continue;
+        }
+        next.shuffle(rng);
+        let (nx, ny) = next[0];
+        blocks.remove(&cell((ox + nx) / 2, (oy + ny) / 2));
+        blocks.remove(&cell(nx, ny));
+        stack.push((nx, ny));
+    }
+    // 每个关键格子连到左上方最近的通道格
+    for &(x, y) in keys {
+        let (ox, oy) = (x - bounds.x_min, y - bounds.y_min);
+        blocks.remove(&(x, y));
+        blocks.remove(&cell(ox - ox % 2, oy));
+        blocks.remove(&cell(ox - ox % 2, oy - oy % 2));
+    }
+    // 拆掉多余的墙，迷宫里拆墙不会破坏连通
+    let mut walls: Vec<(i32, i32)> = blocks.iter().copied().collect();
+    walls.sort();
+    walls.shuffle(rng);
+    while blocks.len() > target {
+        let Some(wall) = walls.pop() else {
+            break;
+        };
+        blocks.remove(&wall);
+    }
+    blocks
+}
@@ -1,65 +1,356 @@
-use crate::control::executor::{Executors, MapPlace};
-use crate::control::play::{send_message, AppMessage};
+use crate::control::ai::Difficulty;
+use crate::control::board::{BoundaryPolicy, Bounds, Terrain};
+use crate::control::editor::{Brush, Editor};
+use crate::control::executor::{Command, Executors, MapPlace};
+use crate::control::game::{Game, GameState};
+use crate::control::generator::{Generator, MapStyle};
+use crate::control::keys::{Action, KeyBindings, KeyRepeat, KEY_CONFIG, REPEAT_INTERVAL};
+use crate::control::level::{Level, Spawns};
+use crate::control::play::SystemClock;
 use eframe::egui;
-use egui::{vec2, CentralPanel, Color32, FontId, RichText, TextStyle, Window};
-use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
+use egui::{vec2, CentralPanel, Color32, FontId, Response, RichText, TextStyle, Ui, Window};
+use std::time::Duration;
+
+// 可选的地图尺寸（宽, 高）
+const MAP_SIZES: [(usize, usize); 3] = [(8, 8), (13, 11), (64, 48)];
+// 编辑器默认读写的关卡文件
+const LEVEL_PATH: &str = "levels/custom.lvl";
 
-#[derive(Default)]
 pub struct MyApp {
     show_confirmation_dialog: bool,
     allowed_to_close: bool,
-    start: bool,
-    timer: i64,
-    pub map: Executors,
-    behave_tx: Option<Sender<AppMessage>>,
-    behave_rx: Option<Receiver<AppMessage>>,
-    end_tx: Option<Sender<AppMessage>>,
-    is_lose: bool,
+    map_size: (usize, usize),
+    policy: BoundaryPolicy,
+    difficulty: Difficulty,
+    generator: Generator,
+    // 保卫基地模式：基地被击中即失败
+    base_defense: bool,
+    pub game: Game,
+    clock: SystemClock,
+    keys: KeyBindings,
+    key_repeat: KeyRepeat,
+    show_key_settings: bool,
+    // 设置窗口中正在等待按键的动作
+    capturing: Option<Action>,
+    key_status: String,
+    // 关卡编辑器
+    editing: bool,
+    editor: Editor,
+    brush: Brush,
+    editor_size: (usize, usize),
+    level_path: String,
+    editor_status: String,
+    // 正在试玩编辑器中的关卡
+    test_play: bool,
 }
 
 impl MyApp {
     fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let (tx, rx) = mpsc::channel();
+        let (keys, key_status) = match KeyBindings::load(KEY_CONFIG) {
+            Ok(keys) => (keys, String::new()),
+            Err(e) => (
+                KeyBindings::default(),
+                format!("Failed to load {}: {}", KEY_CONFIG, e),
+            ),
+        };
         Self {
             show_confirmation_dialog: false,
             allowed_to_close: false,
-            start: false,
-            timer: 0,
-            map: Executors::new(),
-            behave_tx: Some(tx),
-            behave_rx: Some(rx),
-            end_tx: None,
-            is_lose: false,
+            map_size: (13, 11),
+            policy: BoundaryPolicy::Clamp,
+            difficulty: Difficulty::default(),
+            generator: Generator::default(),
+            base_defense: true,
+            game: Game::new(Executors::new().with_base()),
+            clock: SystemClock::new(),
+            keys,
+            key_repeat: KeyRepeat::default(),
+            show_key_settings: false,
+            capturing: None,
+            key_status,
+            editing: false,
+            editor: Editor::default(),
+            brush: Brush::Block,
+            editor_size: (13, 11),
+            level_path: LEVEL_PATH.to_string(),
+            editor_status: String::new(),
+            test_play: false,
         }
     }
-}
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) -> () {
-        if self.map.is_lose == true {
-            let tx = self.end_tx.clone();
-            if let Some(tx) = tx {
-                tx.send(AppMessage::End).unwrap();
+    // 把按住的按键转换成玩家动作，交给游戏处理
+    fn handle_keys(&mut self, ctx: &egui::Context) {
+        if self.game.state() != GameState::Running || self.capturing.is_some() {
+            return;
+        }
+        let (now, held) = ctx.input(|i| {
+            let held: Vec<Action> = Action::ALL
+                .into_iter()
+                .filter(|action| self.keys.keys(*action).iter().any(|k| i.key_down(*k)))
+                .collect();
+            (Duration::from_secs_f64(i.time), held)
+        });
+        for action in self.key_repeat.update(now, &held) {
+            match action.command() {
+                Some(cmd) => self.game.apply_input(cmd),
+                None => {
+                    self.game.fire();
+                }
             }
-            self.map.is_lose = false;
-            self.is_lose = true;
         }
+        if !held.is_empty() {
+            ctx.request_repaint_after(REPEAT_INTERVAL);
+        }
+    }
 
-        self.timer += 1;
-        if self.timer >= 10 {
-            self.timer = 0;
-            if let Some(rx) = &mut self.behave_rx {
-                match rx.try_recv() {
-                    Ok(AppMessage::SpawnEnemy) => self.map.spawn(),
-                    Ok(AppMessage::MoveEnemies) => self.map.enemy_move(),
-                    Ok(AppMessage::Shoot) => self.map.shoot(),
-                    Ok(AppMessage::MoveShoot) => self.map.shoot_move(),
-                    Ok(AppMessage::SpawnBlock) => self.map.spawn_block(),
-                    _ => {}
-                }
+    // 等待下一次按键并绑定到正在设置的动作上，Esc 取消
+    fn capture_key(&mut self, ctx: &egui::Context) {
+        let Some(action) = self.capturing else {
+            return;
+        };
+        let pressed = ctx.input(|i| {
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    repeat: false,
+                    ..
+                } => Some(*key),
+                _ => None,
+            })
+        });
+        if let Some(key) = pressed {
+            if key != egui::Key::Escape {
+                self.keys.bind(action, key);
             }
+            self.capturing = None;
+        }
+    }
+
+    fn key_settings(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_key_settings;
+        Window::new("Key bindings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("key_bindings").show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.label(action.to_string());
+                        let keys: Vec<&str> =
+                            self.keys.keys(action).iter().map(|k| k.name()).collect();
+                        ui.label(keys.join(", "));
+                        if self.capturing == Some(action) {
+                            ui.label("Press a key...");
+                        } else if ui.button("Add").clicked() {
+                            self.capturing = Some(action);
+                        }
+                        if ui.button("Clear").clicked() {
+                            self.keys.clear(action);
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Defaults").clicked() {
+                        self.keys = KeyBindings::default();
+                    }
+                    if ui.button("Save").clicked() {
+                        self.key_status = match self.keys.save(KEY_CONFIG) {
+                            Ok(()) => format!("Saved to {}", KEY_CONFIG),
+                            Err(e) => format!("Save failed: {}", e),
+                        };
+                    }
+                });
+                ui.label(&self.key_status);
+            });
+        if !open {
+            self.capturing = None;
+        }
+        self.show_key_settings = open;
+    }
+
+    // 用编辑器中的关卡开一局试玩
+    fn start_test_play(&mut self) {
+        let level = self.editor.level().clone();
+        if let Err(e) = level.validate() {
+            self.editor_status = format!("Cannot play: {}", e);
+            return;
+        }
+        self.game = Game::new(Executors::from_level(level, rand::random()));
+        self.game.start();
+        self.editing = false;
+        self.test_play = true;
+    }
+
+    fn editor_panel(&mut self, ctx: &egui::Context) {
+        // Ctrl+Z 撤销，Ctrl+Y 重做
+        let (undo, redo) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z),
+                i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y),
+            )
+        });
+        if undo {
+            self.editor.undo();
+        }
+        if redo {
+            self.editor.redo();
+        }
+        CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading(RichText::new("Level Editor").size(32.0));
+                if ui
+                    .add_enabled(self.editor.can_undo(), egui::Button::new("Undo"))
+                    .clicked()
+                {
+                    self.editor.undo();
+                }
+                if ui
+                    .add_enabled(self.editor.can_redo(), egui::Button::new("Redo"))
+                    .clicked()
+                {
+                    self.editor.redo();
+                }
+                if ui.button("Validate").clicked() {
+                    self.editor_status = match self.editor.level().validate() {
+                        Ok(()) => "The map is valid".to_string(),
+                        Err(e) => e,
+                    };
+                }
+                if ui.button("Test play").clicked() {
+                    self.start_test_play();
+                }
+                if ui.button("Back").clicked() {
+                    self.editing = false;
+                    self.test_play = false;
+                    self.back_to_menu();
+                }
+            });
+            ui.horizontal(|ui| {
+                for brush in Brush::ALL {
+                    ui.selectable_value(&mut self.brush, brush, brush.name());
+                }
+            });
+            ui.horizontal(|ui| {
+                let (width, height) = &mut self.editor_size;
+                ui.add(egui::DragValue::new(width).range(1..=64).prefix("W "));
+                ui.add(egui::DragValue::new(height).range(1..=48).prefix("H "));
+                if ui.button("Resize").clicked() {
+                    self.editor.resize(*width, *height);
+                }
+                let mut level = self.editor.level().clone();
+                egui::ComboBox::from_id_source("editor_policy")
+                    .selected_text(format!("{:?}", level.bounds.policy))
+                    .show_ui(ui, |ui| {
+                        for policy in BoundaryPolicy::ALL {
+                            ui.selectable_value(
+                                &mut level.bounds.policy,
+                                policy,
+                                format!("{:?}", policy),
+                            );
+                        }
+                    });
+                egui::ComboBox::from_id_source("editor_difficulty")
+                    .selected_text(format!("{:?}", level.difficulty))
+                    .show_ui(ui, |ui| {
+                        for difficulty in Difficulty::ALL {
+                            ui.selectable_value(
+                                &mut level.difficulty,
+                                difficulty,
+                                format!("{:?}", difficulty),
+                            );
+                        }
+                    });
+                self.editor.set_level(level);
+                ui.text_edit_singleline(&mut self.level_path);
+                if ui.button("Load").clicked() {
+                    self.editor_status = match Level::load(&self.level_path) {
+                        Ok(level) => {
+                            self.editor_size = (level.bounds.width(), level.bounds.height());
+                            self.editor = Editor::new(level);
+                            format!("Loaded {}", self.level_path)
+                        }
+                        Err(e) => format!("Load failed: {}", e),
+                    };
+                }
+                if ui.button("Save").clicked() {
+                    self.editor_status = match self.editor.level().save(&self.level_path) {
+                        // 未通过检查的地图也可以保存，便于分多次完成
+                        Ok(()) => match self.editor.level().validate() {
+                            Ok(()) => format!("Saved to {}", self.level_path),
+                            Err(e) => format!("Saved to {} ({})", self.level_path, e),
+                        },
+                        Err(e) => format!("Save failed: {}", e),
+                    };
+                }
+            });
+            ui.label(&self.editor_status);
+            let level = self.editor.level().clone();
+            let bounds = level.bounds;
+            egui::ScrollArea::both().show(ui, |ui| {
+                egui::Grid::new("editor_grid")
+                    .spacing([2.0, 2.0])
+                    .show(ui, |ui| {
+                        for y in bounds.y_min..=bounds.y_max {
+                            for x in bounds.x_min..=bounds.x_max {
+                                let glyph = level.glyph(x, y).to_string();
+                                let button = egui::Button::new(RichText::new(glyph).monospace())
+                                    .min_size(vec2(24.0, 24.0));
+                                if ui.add(button).clicked() {
+                                    self.editor.paint(x, y, self.brush);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+    }
+
+    // 按菜单中的选项生成一局新游戏，停在主菜单
+    fn back_to_menu(&mut self) {
+        let (width, height) = self.map_size;
+        let bounds = Bounds::new(width, height).with_policy(self.policy);
+        let mut map =
+            Executors::with_generator(bounds, rand::random(), Spawns::new(bounds), self.generator);
+        map.set_difficulty(self.difficulty);
+        if self.base_defense {
+            map = map.with_base();
+        }
+        self.game = Game::new(map);
+    }
+}
+
+// 砖块的损坏程度按剩余耐久度占出现时耐久度的比例分三档：完好、受损、快要碎掉
+fn brick_stage(hp: u32, full: u32) -> (char, Color32) {
+    if 3 * hp > 2 * full {
+        ('#', Color32::from_rgb(180, 80, 40))
+    } else if 3 * hp > full {
+        ('▒', Color32::from_rgb(150, 65, 35))
+    } else {
+        ('░', Color32::from_rgb(120, 50, 30))
+    }
+}
+
+fn big_button(ui: &mut Ui, text: &str, size: egui::Vec2) -> Response {
+    ui.add(
+        egui::Button::new(RichText::new(text).size(36.0))
+            .stroke(egui::Stroke::new(2.0, Color32::RED))
+            .min_size(size),
+    )
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // 按真实时间推进，处理期间到期的全部事件，并在下一个事件到期时重绘
+        self.game.poll(&self.clock);
+        if let Some(wait) = self.game.until_next() {
+            ctx.request_repaint_after(wait);
+        }
+        self.capture_key(ctx);
+        self.handle_keys(ctx);
+        if self.show_key_settings {
+            self.key_settings(ctx);
         }
 
         if ctx.input(|i| i.viewport().close_requested()) {
@@ -89,6 +380,11 @@ impl eframe::App for MyApp {
                 });
         }
 
+        if self.editing {
+            self.editor_panel(ctx);
+            return;
+        }
+
         CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.add_space(135.0);
@@ -97,114 +393,260 @@ impl eframe::App for MyApp {
                         .color(Color32::from_rgb(255, 0, 0))
                         .size(64.0),
                 );
-                let start = ui.add(
-                    egui::Button::new(RichText::new("Start").size(36.0))
-                        .stroke(egui::Stroke::new(2.0, Color32::RED))
-                        .min_size(vec2(200.0, 64.0)),
-                );
-                if start.clicked() {
-                    if self.start == false {
-                        self.start = true;
-                        let tx_clone = self.behave_tx.as_ref().unwrap().clone();
-                        let (end_tx, end_rx) = mpsc::channel();
-                        self.end_tx = Some(end_tx);
-                        thread::spawn(move || {
-                            send_message(tx_clone, end_rx);
+                let state = self.game.state();
+                let button_size = vec2(150.0, 64.0);
+                match state {
+                    GameState::MainMenu => {
+                        if big_button(ui, "Start", button_size).clicked() {
+                            self.game.start();
+                        }
+                    }
+                    GameState::Running => {
+                        if big_button(ui, "Pause", button_size).clicked() {
+                            self.game.pause();
+                        }
+                    }
+                    GameState::Paused => {
+                        if big_button(ui, "Resume", button_size).clicked() {
+                            self.game.resume();
+                        }
+                    }
+                    GameState::GameOver => {}
+                }
+                if matches!(state, GameState::Paused | GameState::GameOver) {
+                    if big_button(ui, "Restart", button_size).clicked() {
+                        self.game.restart();
+                    }
+                    if big_button(ui, "Menu", button_size).clicked() {
+                        self.test_play = false;
+                        self.back_to_menu();
+                    }
+                }
+                // 从主菜单或试玩中进入编辑器
+                if state == GameState::MainMenu || self.test_play {
+                    let label = if self.test_play { "Edit" } else { "Editor" };
+                    if big_button(ui, label, button_size).clicked() {
+                        self.game.pause();
+                        self.editing = true;
+                    }
+                }
+                if state == GameState::MainMenu {
+                    let mut changed = false;
+                    let (width, height) = self.map_size;
+                    egui::ComboBox::from_id_source("map_size")
+                        .selected_text(format!("{}x{}", width, height))
+                        .show_ui(ui, |ui| {
+                            for (width, height) in MAP_SIZES {
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.map_size,
+                                        (width, height),
+                                        format!("{}x{}", width, height),
+                                    )
+                                    .changed();
+                            }
+                        });
+                    egui::ComboBox::from_id_source("boundary_policy")
+                        .selected_text(format!("{:?}", self.policy))
+                        .show_ui(ui, |ui| {
+                            for policy in BoundaryPolicy::ALL {
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.policy,
+                                        policy,
+                                        format!("{:?}", policy),
+                                    )
+                                    .changed();
+                            }
+                        });
+                    egui::ComboBox::from_id_source("difficulty")
+                        .selected_text(format!("{:?}", self.difficulty))
+                        .show_ui(ui, |ui| {
+                            for difficulty in Difficulty::ALL {
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut self.difficulty,
+                                        difficulty,
+                                        format!("{:?}", difficulty),
+                                    )
+                                    .changed();
+                            }
                         });
+                    egui::ComboBox::from_id_source("map_style")
+                        .selected_text(format!("{:?}", self.generator.style))
+                        .show_ui(ui, |ui| {
+                            for style in MapStyle::ALL {
+                                if ui
+                                    .selectable_label(
+                                        self.generator.style == style,
+                                        format!("{:?}", style),
+                                    )
+                                    .clicked()
+                                {
+                                    self.generator = Generator::new(style);
+                                    changed = true;
+                                }
+                            }
+                        });
+                    // 拖动结束后才重新生成地图
+                    let density = ui.add(
+                        egui::Slider::new(&mut self.generator.density, 0.0..=0.6).text("Density"),
+                    );
+                    changed |= density.drag_stopped() || (density.changed() && !density.dragged());
+                    changed |= ui.checkbox(&mut self.base_defense, "Base").changed();
+                    if changed {
+                        self.back_to_menu();
                     }
                 }
+                // 显示种子，便于复现问题
+                ui.label(format!("Seed:{}", self.game.map().seed()));
+                if ui.button("Keys").clicked() {
+                    self.show_key_settings = true;
+                }
                 ui.label(
-                    RichText::new(format!("Point:{}", self.map.point))
+                    RichText::new(format!("Point:{}", self.game.point()))
                         .color(Color32::from_rgb(255, 0, 0))
                         .size(64.0),
                 );
-                if self.is_lose {
+                let map = self.game.map();
+                ui.label(
+                    RichText::new(format!("HP:{} Lives:{}", map.player_hp(), map.lives()))
+                        .color(Color32::GREEN)
+                        .size(32.0),
+                );
+                ui.label(
+                    RichText::new(format!(
+                        "Wave:{} Left:{}",
+                        map.wave(),
+                        map.enemies_remaining()
+                    ))
+                    .size(32.0),
+                );
+                // 生效中的道具及剩余秒数
+                let effects: Vec<String> = map
+                    .effects()
+                    .iter()
+                    .map(|(power_up, left)| {
+                        format!("{:?}:{}s", power_up, left.as_secs_f32().ceil())
+                    })
+                    .collect();
+                if !effects.is_empty() {
                     ui.label(
-                        RichText::new("  Game Over")
+                        RichText::new(effects.join(" "))
+                            .color(Color32::GOLD)
+                            .size(32.0),
+                    );
+                }
+                // 结束时一并显示到达的波次
+                let banner = match state {
+                    GameState::Paused => "  Paused".to_string(),
+                    GameState::GameOver if self.game.map().is_base_destroyed() => {
+                        format!("  Base Destroyed  Wave {}", self.game.wave())
+                    }
+                    GameState::GameOver => format!("  Game Over  Wave {}", self.game.wave()),
+                    _ => String::new(),
+                };
+                if !banner.is_empty() {
+                    ui.label(
+                        RichText::new(banner)
                             .color(Color32::from_rgb(255, 0, 0))
                             .size(64.0),
                     );
                 }
             });
             ui.horizontal(|ui| {
-                ui.add_space(440.0);
+                // 每格 20 宽加 10 间距，居中显示
+                let grid_width = self.game.map().bounds().width() as f32 * 30.0;
+                ui.add_space(((ui.available_width() - grid_width) / 2.0).max(0.0));
                 let font_id = FontId::monospace(25.0);
                 ui.style_mut().override_text_style = Some(TextStyle::Monospace);
                 ui.style_mut()
                     .text_styles
                     .insert(TextStyle::Monospace, font_id);
-                egui::Grid::new("array_grid")
-                    .min_col_width(20.0)
-                    .spacing([10.0, 10.0])
+                let grid_height = (ui.available_height() - 140.0).max(100.0);
+                egui::ScrollArea::both()
+                    .max_height(grid_height)
                     .show(ui, |ui| {
-                        for row in &self.map.executors {
-                            for cell in row {
-                                if let MapPlace::Place = cell {
-                                    ui.label("+".to_string());
-                                } else if let MapPlace::Block = cell {
-                                    ui.label("#".to_string());
-                                } else if let MapPlace::Player(player) = cell {
-                                    let (_, _, heading) = player.query();
-                                    ui.colored_label(
-                                        Color32::GREEN,
-                                        match heading {
-                                            'E' => '>',
-                                            'S' => 'v',
-                                            'W' => '<',
-                                            'N' => '^',
-                                            _ => 'N',
-                                        }
-                                        .to_string(),
-                                    );
-                                } else if let MapPlace::Enemy(enemy) = cell {
-                                    let (_, _, heading) = enemy.query();
-                                    ui.colored_label(
-                                        Color32::RED,
-                                        match heading {
-                                            'E' => '>',
-                                            'S' => 'v',
-                                            'W' => '<',
-                                            'N' => '^',
-                                            _ => 'N',
+                        egui::Grid::new("array_grid")
+                            .min_col_width(20.0)
+                            .spacing([10.0, 10.0])
+                            .show(ui, |ui| {
+                                let board = &self.game.map().executors;
+                                let bounds = board.bounds();
+                                for y in bounds.y_min..=bounds.y_max {
+                                    for x in bounds.x_min..=bounds.x_max {
+                                        let cell = board[(x, y)];
+                                        let terrain = board.terrain(x, y).unwrap_or_default();
+                                        // 树林遮住其中的单位和子弹
+                                        if terrain == Terrain::Forest {
+                                            ui.colored_label(
+                                                Color32::DARK_GREEN,
+                                                terrain.glyph().to_string(),
+                                            );
+                                        } else if let MapPlace::Place = cell {
+                                            let color = match terrain {
+                                                Terrain::Water => Color32::BLUE,
+                                                Terrain::Ice => Color32::from_rgb(170, 220, 255),
+                                                _ => ui.visuals().text_color(),
+                                            };
+                                            ui.colored_label(color, terrain.glyph().to_string());
+                                        } else if let Some((hp, full)) =
+                                            self.game.map().block_durability(x, y)
+                                        {
+                                            let (glyph, color) = brick_stage(hp, full);
+                                            ui.colored_label(color, glyph.to_string());
+                                        } else if let MapPlace::Steel = cell {
+                                            ui.colored_label(Color32::GRAY, "=".to_string());
+                                        } else if let MapPlace::Base = cell {
+                                            ui.colored_label(Color32::YELLOW, "H".to_string());
+                                        } else if let MapPlace::Pickup(power_up) = cell {
+                                            ui.colored_label(
+                                                Color32::GOLD,
+                                                power_up.glyph().to_string(),
+                                            );
+                                        } else if let MapPlace::Player(player) = cell {
+                                            let glyph = player
+                                                .query()
+                                                .map_or(' ', |(_, _, heading)| heading.glyph());
+                                            // 无敌期间换一种颜色
+                                            let color = if self.game.map().is_invulnerable() {
+                                                Color32::LIGHT_BLUE
+                                            } else {
+                                                Color32::GREEN
+                                            };
+                                            ui.colored_label(color, glyph.to_string());
+                                        } else if let MapPlace::Enemy(enemy) = cell {
+                                            let glyph = enemy
+                                                .query()
+                                                .map_or(' ', |(_, _, heading)| heading.glyph());
+                                            ui.colored_label(Color32::RED, glyph.to_string());
+                                        } else if let MapPlace::Shoot(_shoot) = cell {
+                                            ui.colored_label(Color32::BROWN, "·".to_string());
                                         }
-                                        .to_string(),
-                                    );
-                                } else if let MapPlace::Shoot(_shoot) = cell {
-                                    ui.colored_label(Color32::BROWN, "·".to_string());
+                                    }
+                                    ui.end_row();
                                 }
-                            }
-                            ui.end_row();
-                        }
+                            });
                     });
             });
             ui.vertical(|ui| {
                 ui.horizontal(|ui| {
                     ui.add_space(26.0);
-                    let left = ui.add(
-                        egui::Button::new(RichText::new("L").size(36.0))
-                            .stroke(egui::Stroke::new(2.0, Color32::RED))
-                            .min_size(vec2(360.0, 120.0)),
-                    );
-                    if left.clicked() && self.start == true {
-                        self.map.player_move("L");
+                    let button_size = vec2(280.0, 120.0);
+                    if big_button(ui, "L", button_size).clicked() {
+                        self.game.apply_input(Command::Left);
                     }
-                    ui.add_space(57.0);
-                    let straight = ui.add(
-                        egui::Button::new(RichText::new("M").size(36.0))
-                            .stroke(egui::Stroke::new(2.0, Color32::RED))
-                            .min_size(vec2(360.0, 120.0)),
-                    );
-                    if straight.clicked() && self.start == true {
-                        self.map.player_move("M");
+                    ui.add_space(30.0);
+                    if big_button(ui, "M", button_size).clicked() {
+                        self.game.apply_input(Command::Move);
                     }
-                    ui.add_space(57.0);
-                    let right = ui.add(
-                        egui::Button::new(RichText::new("R").size(36.0))
-                            .stroke(egui::Stroke::new(2.0, Color32::RED))
-                            .min_size(vec2(360.0, 120.0)),
-                    );
-                    if right.clicked() && self.start == true {
-                        self.map.player_move("R");
+                    ui.add_space(30.0);
+                    if big_button(ui, "R", button_size).clicked() {
+                        self.game.apply_input(Command::Right);
+                    }
+                    ui.add_space(30.0);
+                    if big_button(ui, "Fire", button_size).clicked() {
+                        self.game.fire();
                     }
                 })
             })
@@ -0,0 +1,200 @@
+use crate::control::executor::Command;
+use eframe::egui::Key;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+// 按键绑定的保存位置（相对于工作目录）
+pub const KEY_CONFIG: &str = "keybindings.cfg";
+
+// 按住按键时，同一动作重复触发的最短间隔
+pub const REPEAT_INTERVAL: Duration = Duration::from_millis(200);
+
+// 可绑定按键的玩家动作
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Move,
+    Left,
+    Right,
+    TurnRound,
+    Fire,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Move,
+        Action::Left,
+        Action::Right,
+        Action::TurnRound,
+        Action::Fire,
+    ];
+
+    // 对应的移动指令；开火不是移动指令
+    pub fn command(self) -> Option<Command> {
+        match self {
+            Action::Move => Some(Command::Move),
+            Action::Left => Some(Command::Left),
+            Action::Right => Some(Command::Right),
+            Action::TurnRound => Some(Command::TurnRound),
+            Action::Fire => None,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .into_iter()
+            .find(|action| action.to_string() == s)
+            .ok_or_else(|| format!("unknown action {:?}", s))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<Key>>,
+}
+
+impl Default for KeyBindings {
+    // 方向键和 WASD 控制移动与转向，空格开火
+    fn default() -> Self {
+        let bindings = HashMap::from([
+            (Action::Move, vec![Key::ArrowUp, Key::W]),
+            (Action::Left, vec![Key::ArrowLeft, Key::A]),
+            (Action::Right, vec![Key::ArrowRight, Key::D]),
+            (Action::TurnRound, vec![Key::ArrowDown, Key::S]),
+            (Action::Fire, vec![Key::Space]),
+        ]);
+        KeyBindings { bindings }
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.bindings
+            .get(&action)
+            .map_or(&[], |keys| keys.as_slice())
+    }
+
+    // 把按键绑定到动作上，同一按键只能属于一个动作
+    pub fn bind(&mut self, action: Action, key: Key) {
+        for keys in self.bindings.values_mut() {
+            keys.retain(|k| *k != key);
+        }
+        self.bindings.entry(action).or_default().push(key);
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn action(&self, key: Key) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| self.keys(*action).contains(&key))
+    }
+
+    // 读取配置文件，文件不存在时使用默认绑定
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => text
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(KeyBindings::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+// 每行一个动作，例如 `Move = Up, W`
+impl fmt::Display for KeyBindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for action in Action::ALL {
+            let keys: Vec<&str> = self.keys(action).iter().map(|k| k.name()).collect();
+            writeln!(f, "{} = {}", action, keys.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for KeyBindings {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bindings = KeyBindings {
+            bindings: HashMap::new(),
+        };
+        for (line_no, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (action, keys) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `Action = Key, ...`", line_no + 1))?;
+            let action: Action = action
+                .trim()
+                .parse()
+                .map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+            for key in keys.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+                let key = Key::from_name(key)
+                    .ok_or_else(|| format!("line {}: unknown key {:?}", line_no + 1, key))?;
+                bindings.bind(action, key);
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+// 限制按住按键时的重复频率：刚按下立即触发，之后每隔 interval 触发一次
+#[derive(Debug, Clone)]
+pub struct KeyRepeat {
+    interval: Duration,
+    last: HashMap<Action, Duration>,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        KeyRepeat::new(REPEAT_INTERVAL)
+    }
+}
+
+impl KeyRepeat {
+    pub fn new(interval: Duration) -> Self {
+        KeyRepeat {
+            interval,
+            last: HashMap::new(),
+        }
+    }
+
+    // held 为此刻按住的动作，返回本帧应当触发的动作
+    pub fn update(&mut self, now: Duration, held: &[Action]) -> Vec<Action> {
+        self.last.retain(|action, _| held.contains(action));
+        let mut fired = Vec::new();
+        for action in held {
+            let due = match self.last.get(action) {
+                Some(last) => now.saturating_sub(*last) >= self.interval,
+                None => true,
+            };
+            if due && !fired.contains(action) {
+                self.last.insert(*action, now);
+                fired.push(*action);
+            }
+        }
+        fired
+    }
+}
@@ -0,0 +1,452 @@
+use crate::control::ai::{self, Difficulty};
+use crate::control::behavior::BehaviorKind;
+use crate::control::board::{BoundaryPolicy, Bounds, Terrain};
+use crate::control::executor::{Heading, BLOCK_HP};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+// 敌人不会出生在离玩家这么近（曼哈顿距离）的地方
+pub const SPAWN_SAFE_DISTANCE: i32 = 2;
+
+// 敌人出生点及在此出生的敌人的行为，behavior 为 None 时由当前波次决定
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpawnPoint {
+    pub x: i32,
+    pub y: i32,
+    pub behavior: Option<BehaviorKind>,
+}
+
+impl SpawnPoint {
+    pub fn new(x: i32, y: i32) -> Self {
+        SpawnPoint {
+            x,
+            y,
+            behavior: None,
+        }
+    }
+}
+
+// 一张地图上玩家和敌人的出生点，玩家开局出生在第一个出生点
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spawns {
+    pub player: Vec<(i32, i32)>,
+    pub enemies: Vec<SpawnPoint>,
+}
+
+impl Spawns {
+    // 默认布局：玩家在底边中间，敌人在顶行左右两侧
+    pub fn new(bounds: Bounds) -> Self {
+        Spawns {
+            player: vec![(0, bounds.y_max)],
+            enemies: vec![
+                SpawnPoint::new(bounds.x_max - 2, bounds.y_min),
+                SpawnPoint::new(bounds.x_min + 2, bounds.y_min),
+            ],
+        }
+    }
+}
+
+// 关卡文件中表头与地图之间的分隔行
+const SEPARATOR: &str = "---";
+// 钢块在关卡文件中的字符
+const STEEL: char = '=';
+// 砖块在关卡文件中的字符：`#` 为默认耐久度，数字为指定的耐久度
+const BRICK: char = '#';
+// 关卡文件中能指定的最大耐久度
+pub const MAX_BLOCK_HP: u32 = 9;
+// 基地在关卡文件中的字符
+const BASE: char = 'H';
+
+// 关卡文件的解析错误，行号和列号从 1 开始
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl LevelError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        LevelError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+// 手工制作的地图：大小、设置、方块、地形和出生点
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level {
+    pub bounds: Bounds,
+    pub difficulty: Difficulty,
+    // 玩家开局的朝向，开局位置为 spawns.player[0]
+    pub heading: Heading,
+    // 砖块及其初始耐久度
+    pub blocks: Vec<((i32, i32), u32)>,
+    pub steel: Vec<(i32, i32)>,
+    // 非普通地面的格子，出生点所在的格子总是普通地面
+    pub terrain: Vec<((i32, i32), Terrain)>,
+    // 要保护的基地，None 时为没有基地的生存模式
+    pub base: Option<(i32, i32)>,
+    pub spawns: Spawns,
+}
+
+impl Level {
+    // 只有默认出生点的空地图
+    pub fn new(bounds: Bounds) -> Self {
+        Level {
+            bounds,
+            difficulty: Difficulty::default(),
+            heading: Heading::N,
+            blocks: Vec::new(),
+            steel: Vec::new(),
+            terrain: Vec::new(),
+            base: None,
+            spawns: Spawns::new(bounds),
+        }
+    }
+
+    // 检查关卡能否游玩：要有玩家和敌人出生点，且所有出生点都能从玩家开局位置走到
+    pub fn validate(&self) -> Result<(), String> {
+        let bounds = self.bounds;
+        let Some(&start) = self.spawns.player.first() else {
+            return Err("the map has no player".to_string());
+        };
+        if self.spawns.enemies.is_empty() {
+            return Err("the map has no enemy spawn point".to_string());
+        }
+        let spawns = self
+            .spawns
+            .player
+            .iter()
+            .copied()
+            .chain(self.spawns.enemies.iter().map(|p| (p.x, p.y)));
+        for (x, y) in spawns.clone() {
+            if !bounds.contains(x, y) {
+                return Err(format!("spawn point ({}, {}) is outside the map", x, y));
+            }
+            if self.block_at(x, y).is_some() || self.steel.contains(&(x, y)) {
+                return Err(format!("spawn point ({}, {}) is on a block", x, y));
+            }
+            match self.terrain_at(x, y) {
+                Terrain::Ground => {}
+                Terrain::Water => return Err(format!("spawn point ({}, {}) is in water", x, y)),
+                terrain => {
+                    return Err(format!("spawn point ({}, {}) is on {:?}", x, y, terrain));
+                }
+            }
+            if self.base == Some((x, y)) {
+                return Err(format!("spawn point ({}, {}) is on the base", x, y));
+            }
+        }
+        if let Some((x, y)) = self.base {
+            if !bounds.contains(x, y) {
+                return Err(format!("the base ({}, {}) is outside the map", x, y));
+            }
+        }
+        // 从玩家开局位置出发，坦克能走到的格子
+        let blocked: HashSet<(i32, i32)> = self
+            .blocks
+            .iter()
+            .map(|(cell, _)| *cell)
+            .chain(self.steel.iter().copied())
+            .chain(
+                self.terrain
+                    .iter()
+                    .filter(|(_, terrain)| *terrain == Terrain::Water)
+                    .map(|(cell, _)| *cell),
+            )
+            .chain(self.base)
+            .collect();
+        let reached = ai::reachable(bounds, start, |cell| !blocked.contains(&cell));
+        match spawns.into_iter().find(|cell| !reached.contains(cell)) {
+            Some((x, y)) => Err(format!("spawn point ({}, {}) is unreachable", x, y)),
+            None => Ok(()),
+        }
+    }
+
+    // (x, y) 在关卡文件中的字符
+    pub fn glyph(&self, x: i32, y: i32) -> char {
+        let spawn = self.spawns.enemies.iter().find(|p| (p.x, p.y) == (x, y));
+        if self.spawns.player.first() == Some(&(x, y)) {
+            self.heading.glyph()
+        } else if self.spawns.player.contains(&(x, y)) {
+            '@'
+        } else if let Some(spawn) = spawn {
+            spawn_glyph(spawn.behavior)
+        } else if self.base == Some((x, y)) {
+            BASE
+        } else if let Some(hp) = self.block_at(x, y) {
+            block_glyph(hp)
+        } else if self.steel.contains(&(x, y)) {
+            STEEL
+        } else {
+            self.terrain_at(x, y).glyph()
+        }
+    }
+
+    // (x, y) 上砖块的初始耐久度
+    pub fn block_at(&self, x: i32, y: i32) -> Option<u32> {
+        self.blocks
+            .iter()
+            .find(|(cell, _)| *cell == (x, y))
+            .map(|(_, hp)| *hp)
+    }
+
+    pub fn terrain_at(&self, x: i32, y: i32) -> Terrain {
+        self.terrain
+            .iter()
+            .find(|(cell, _)| *cell == (x, y))
+            .map_or(Terrain::Ground, |(_, terrain)| *terrain)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+// 敌人出生点的标记：E 由波次决定行为，小写字母指定行为
+fn spawn_glyph(behavior: Option<BehaviorKind>) -> char {
+    match behavior {
+        None => 'E',
+        Some(BehaviorKind::RandomWalker) => 'w',
+        Some(BehaviorKind::Patroller) => 'p',
+        Some(BehaviorKind::Chaser) => 'c',
+        Some(BehaviorKind::Sniper) => 's',
+        Some(BehaviorKind::Guard) => 'g',
+    }
+}
+
+// 耐久度超过 9 的砖块按 9 保存
+fn block_glyph(hp: u32) -> char {
+    if hp == BLOCK_HP {
+        BRICK
+    } else {
+        char::from_digit(hp.clamp(1, MAX_BLOCK_HP), 10).unwrap()
+    }
+}
+
+fn heading_from_glyph(c: char) -> Option<Heading> {
+    [Heading::N, Heading::E, Heading::S, Heading::W]
+        .into_iter()
+        .find(|heading| heading.glyph() == c)
+}
+
+// 按 Debug 名称查找，用于表头中的枚举值
+fn parse_name<T: fmt::Debug + Copy>(all: &[T], s: &str) -> Option<T> {
+    all.iter().copied().find(|v| format!("{:?}", v) == s)
+}
+
+// 表头每行一个 `键 = 值`，之后是分隔行和地图：
+// `+` 空地，`#` 砖块，`1`-`9` 指定耐久度的砖块，`=` 钢块，`~` 水面，`%` 树林，`_` 冰面，`H` 基地，`^>v<` 玩家，`@` 额外的玩家出生点，`E`/`wpcsg` 敌人出生点
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bounds = self.bounds;
+        writeln!(f, "size = {}x{}", bounds.width(), bounds.height())?;
+        writeln!(f, "policy = {:?}", bounds.policy)?;
+        writeln!(f, "difficulty = {:?}", self.difficulty)?;
+        writeln!(f, "{}", SEPARATOR)?;
+        for y in bounds.y_min..=bounds.y_max {
+            let row: String = (bounds.x_min..=bounds.x_max)
+                .map(|x| self.glyph(x, y))
+                .collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Level {
+    type Err = LevelError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate().map(|(i, line)| (i + 1, line));
+        let mut size = None;
+        let mut policy = BoundaryPolicy::default();
+        let mut difficulty = Difficulty::default();
+        // 表头，允许空行和以 # 开头的注释
+        let mut separator_line = 0;
+        for (line_no, line) in lines.by_ref() {
+            let trimmed = line.trim();
+            if trimmed == SEPARATOR {
+                separator_line = line_no;
+                break;
+            }
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| LevelError::new(line_no, 1, "expected `key = value`"))?;
+            let column = key.len() + 2 + value.len() - value.trim_start().len();
+            let value = value.trim();
+            match key.trim() {
+                "size" => {
+                    let parsed = value
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+                        .filter(|(w, h): &(usize, usize)| *w > 0 && *h > 0);
+                    size = Some(parsed.ok_or_else(|| {
+                        LevelError::new(line_no, column, format!("bad size {:?}", value))
+                    })?);
+                }
+                "policy" => {
+                    policy = parse_name(&BoundaryPolicy::ALL, value).ok_or_else(|| {
+                        LevelError::new(line_no, column, format!("unknown policy {:?}", value))
+                    })?;
+                }
+                "difficulty" => {
+                    difficulty = parse_name(&Difficulty::ALL, value).ok_or_else(|| {
+                        LevelError::new(line_no, column, format!("unknown difficulty {:?}", value))
+                    })?;
+                }
+                other => {
+                    return Err(LevelError::new(
+                        line_no,
+                        1,
+                        format!("unknown setting {:?}", other),
+                    ))
+                }
+            }
+        }
+        if separator_line == 0 {
+            let last = s.lines().count().max(1);
+            return Err(LevelError::new(last, 1, format!("missing `{}`", SEPARATOR)));
+        }
+        let (width, height) =
+            size.ok_or_else(|| LevelError::new(separator_line, 1, "missing `size = WxH`"))?;
+        let bounds = Bounds::new(width, height).with_policy(policy);
+
+        let mut heading_at = None;
+        let mut extra_players = Vec::new();
+        let mut blocks = Vec::new();
+        let mut steel = Vec::new();
+        let mut terrain = Vec::new();
+        let mut base = None;
+        let mut enemies = Vec::new();
+        let mut rows = 0;
+        for (line_no, line) in lines {
+            if rows == height {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return Err(LevelError::new(
+                    line_no,
+                    1,
+                    format!("expected {} rows", height),
+                ));
+            }
+            let y = bounds.y_min + rows as i32;
+            let mut columns = 0;
+            for (i, c) in line.trim_end().chars().enumerate() {
+                if i == width {
+                    return Err(LevelError::new(
+                        line_no,
+                        i + 1,
+                        format!("expected {} columns", width),
+                    ));
+                }
+                let x = bounds.x_min + i as i32;
+                match c {
+                    '+' => {}
+                    BRICK => blocks.push(((x, y), BLOCK_HP)),
+                    '1'..='9' => blocks.push(((x, y), c.to_digit(10).unwrap())),
+                    STEEL => steel.push((x, y)),
+                    BASE => {
+                        if base.is_some() {
+                            return Err(LevelError::new(line_no, i + 1, "more than one base"));
+                        }
+                        base = Some((x, y));
+                    }
+                    '@' => extra_players.push((x, y)),
+                    'E' => enemies.push(SpawnPoint::new(x, y)),
+                    _ => {
+                        if let Some(heading) = heading_from_glyph(c) {
+                            if heading_at.is_some() {
+                                return Err(LevelError::new(
+                                    line_no,
+                                    i + 1,
+                                    "more than one player",
+                                ));
+                            }
+                            heading_at = Some((heading, (x, y)));
+                        } else if let Some(kind) = Terrain::ALL
+                            .into_iter()
+                            .find(|kind| *kind != Terrain::Ground && kind.glyph() == c)
+                        {
+                            terrain.push(((x, y), kind));
+                        } else if let Some(behavior) = BehaviorKind::ALL
+                            .into_iter()
+                            .find(|kind| spawn_glyph(Some(*kind)) == c)
+                        {
+                            enemies.push(SpawnPoint {
+                                x,
+                                y,
+                                behavior: Some(behavior),
+                            });
+                        } else {
+                            return Err(LevelError::new(
+                                line_no,
+                                i + 1,
+                                format!("unknown glyph {:?}", c),
+                            ));
+                        }
+                    }
+                }
+                columns += 1;
+            }
+            if columns < width {
+                return Err(LevelError::new(
+                    line_no,
+                    columns + 1,
+                    format!("expected {} columns", width),
+                ));
+            }
+            rows += 1;
+        }
+        if rows < height {
+            let last = s.lines().count();
+            return Err(LevelError::new(
+                last + 1,
+                1,
+                format!("expected {} rows, found {}", height, rows),
+            ));
+        }
+        let (heading, start) = heading_at
+            .ok_or_else(|| LevelError::new(separator_line, 1, "the map has no player"))?;
+        let mut player = vec![start];
+        player.extend(extra_players);
+        Ok(Level {
+            bounds,
+            difficulty,
+            heading,
+            blocks,
+            steel,
+            terrain,
+            base,
+            spawns: Spawns { player, enemies },
+        })
+    }
+}
@@ -1,3 +1,13 @@
+pub mod ai;
+pub mod behavior;
+pub mod board;
+pub mod editor;
 pub mod executor;
+pub mod game;
+pub mod generator;
 pub mod gui;
+pub mod keys;
+pub mod level;
 pub mod play;
+pub mod powerup;
+pub mod wave;
@@ -1,73 +1,191 @@
-use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AppMessage {
     SpawnBlock,
     SpawnEnemy,
     MoveEnemies,
     MoveShoot,
     Shoot,
-    End,
+    // 波间休息结束，开始下一波
+    NextWave,
+    SpawnPickup,
 }
 
-pub fn send_message(tx: Sender<AppMessage>, rx: Receiver<AppMessage>) {
-    let (t, r) = mpsc::channel();
-    for i in 0..5 {
-        let t_clone = t.clone();
-        match i {
-            0 => {
-                thread::spawn(move || loop {
-                    t_clone.send(AppMessage::SpawnEnemy).expect("接收者已drop");
-                    thread::sleep(Duration::from_secs(10))
-                });
-            }
-            1 => {
-                thread::spawn(move || {
-                    thread::sleep(Duration::from_secs(2));
-                    loop {
-                        thread::sleep(Duration::from_secs(1));
-                        t_clone.send(AppMessage::MoveEnemies).expect("接收者已drop")
-                    }
-                });
-            }
-            2 => {
-                thread::spawn(move || {
-                    thread::sleep(Duration::from_secs(3));
-                    loop {
-                        thread::sleep(Duration::from_millis(4500));
-                        t_clone.send(AppMessage::Shoot).expect("接收者已drop");
-                    }
-                });
-            }
-            3 => {
-                thread::spawn(move || {
-                    thread::sleep(Duration::from_secs(4));
-                    loop {
-                        thread::sleep(Duration::from_millis(1000));
-                        t_clone.send(AppMessage::MoveShoot).expect("接收者已drop");
-                    }
-                });
-            }
-            4 => {
-                thread::spawn(move || {
-                    thread::sleep(Duration::from_secs(10));
-                    loop {
-                        thread::sleep(Duration::from_secs(10));
-                        t_clone.send(AppMessage::SpawnBlock).expect("接收者已drop");
-                    }
-                });
-            }
-            _ => {}
+// 调度器读取时间的来源
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+// 真实时间
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock {
+            start: Instant::now(),
         }
     }
-    while let Ok(msg) = r.recv() {
-        if let Ok(_msg) = rx.try_recv() {
-            drop(tx);
-            break;
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// 手动拨动的虚拟时间，用于测试
+#[derive(Debug, Default)]
+pub struct VirtualClock {
+    now: Duration,
+}
+
+impl VirtualClock {
+    pub fn advance(&mut self, dt: Duration) {
+        self.now += dt;
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+}
+
+struct Recurring<E> {
+    name: String,
+    event: E,
+    next_due: Duration,
+    period: Duration,
+}
+
+// 管理具名周期事件的单一调度器；内部时间只在未暂停时按倍率前进
+pub struct Scheduler<E> {
+    events: Vec<Recurring<E>>,
+    now: Duration,
+    last_seen: Option<Duration>,
+    paused: bool,
+    time_scale: f64,
+    stopped: bool,
+}
+
+impl<E: Copy> Default for Scheduler<E> {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+impl<E: Copy> Scheduler<E> {
+    pub fn new() -> Self {
+        Scheduler {
+            events: Vec::new(),
+            now: Duration::ZERO,
+            last_seen: None,
+            paused: false,
+            time_scale: 1.0,
+            stopped: false,
         }
-        tx.send(msg).unwrap();
+    }
+
+    // 注册周期事件：delay 后首次触发，之后每隔 period 触发；同名事件会被替换
+    pub fn every(&mut self, name: &str, event: E, delay: Duration, period: Duration) {
+        assert!(!period.is_zero(), "period of {:?} must not be zero", name);
+        self.cancel(name);
+        self.events.push(Recurring {
+            name: name.to_string(),
+            event,
+            next_due: self.now + delay,
+            period,
+        });
+    }
+
+    pub fn cancel(&mut self, name: &str) -> bool {
+        let len = self.events.len();
+        self.events.retain(|e| e.name != name);
+        self.events.len() != len
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // 时间倍率，2.0 表示游戏时间以两倍速度流逝
+    pub fn set_time_scale(&mut self, scale: f64) {
+        assert!(scale >= 0.0, "time scale must not be negative");
+        self.time_scale = scale;
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    // 清空所有事件，此后不再产生任何事件
+    pub fn shutdown(&mut self) {
+        self.events.clear();
+        self.stopped = true;
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.stopped
+    }
+
+    // 调度器内部的游戏时间
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    // 距下一个事件到期还需经过的真实时间；暂停或没有事件时返回 None
+    pub fn until_next(&self) -> Option<Duration> {
+        if self.paused || self.time_scale == 0.0 {
+            return None;
+        }
+        self.events
+            .iter()
+            .map(|e| e.next_due.saturating_sub(self.now))
+            .min()
+            .map(|d| d.div_f64(self.time_scale))
+    }
+
+    // 让真实时间流逝 dt，按到期顺序返回其间所有到期的事件
+    pub fn advance(&mut self, dt: Duration) -> Vec<E> {
+        if self.stopped {
+            return Vec::new();
+        }
+        if !self.paused {
+            self.now += dt.mul_f64(self.time_scale);
+        }
+        let mut due = Vec::new();
+        for (i, e) in self.events.iter_mut().enumerate() {
+            while e.next_due <= self.now {
+                due.push((e.next_due, i, e.event));
+                e.next_due += e.period;
+            }
+        }
+        due.sort_by_key(|(at, i, _)| (*at, *i));
+        due.into_iter().map(|(_, _, event)| event).collect()
+    }
+
+    // 以时钟读数的差值推进
+    pub fn poll(&mut self, clock: &impl Clock) -> Vec<E> {
+        let now = clock.now();
+        let dt = now.saturating_sub(self.last_seen.unwrap_or(now));
+        self.last_seen = Some(now);
+        self.advance(dt)
     }
 }
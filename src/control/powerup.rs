@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+// 敌人被消灭时留下道具的概率
+pub const PICKUP_DROP_CHANCE: f64 = 0.25;
+// 场上同时存在的道具上限，超过时不再定时刷新
+pub const MAX_PICKUPS: usize = 2;
+// 连射期间开火冷却缩短为原来的几分之一
+pub const RAPID_FIRE_DIVISOR: u32 = 3;
+
+// 玩家开到道具上即可拾取
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PowerUp {
+    // 护盾：期间不受伤害
+    Shield,
+    // 连射：开火冷却缩短
+    RapidFire,
+    // 冻结：所有敌人停止移动和开火
+    Freeze,
+    // 多一条命
+    ExtraLife,
+    // 炸弹：消灭场上所有敌人
+    Bomb,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 5] = [
+        PowerUp::Shield,
+        PowerUp::RapidFire,
+        PowerUp::Freeze,
+        PowerUp::ExtraLife,
+        PowerUp::Bomb,
+    ];
+
+    // 持续效果的时长，立即生效的道具为 None
+    pub fn duration(self) -> Option<Duration> {
+        match self {
+            PowerUp::Shield => Some(Duration::from_secs(10)),
+            PowerUp::RapidFire => Some(Duration::from_secs(10)),
+            PowerUp::Freeze => Some(Duration::from_secs(5)),
+            PowerUp::ExtraLife | PowerUp::Bomb => None,
+        }
+    }
+
+    // GUI 中的字符
+    pub fn glyph(self) -> char {
+        match self {
+            PowerUp::Shield => 'S',
+            PowerUp::RapidFire => 'R',
+            PowerUp::Freeze => 'F',
+            PowerUp::ExtraLife => 'L',
+            PowerUp::Bomb => 'B',
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::control::behavior::BehaviorKind;
+use std::time::Duration;
+
+// 每清完一波获得的分数为 WAVE_BONUS 乘以波次
+pub const WAVE_BONUS: i32 = 5;
+// 一波敌人数量的上限
+pub const MAX_WAVE_SIZE: usize = 30;
+
+// 一波敌人的数量和组成，波次越高敌人越多、种类越多
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wave {
+    pub number: u32,
+    pub enemies: Vec<BehaviorKind>,
+}
+
+impl Wave {
+    // 波次从 1 开始
+    pub fn new(number: u32) -> Self {
+        let number = number.max(1);
+        // 随波次逐步加入新的敌人种类
+        let kinds: &[BehaviorKind] = match number {
+            1 => &[BehaviorKind::RandomWalker, BehaviorKind::Chaser],
+            2 => &[
+                BehaviorKind::RandomWalker,
+                BehaviorKind::Chaser,
+                BehaviorKind::Patroller,
+            ],
+            3 => &[
+                BehaviorKind::Chaser,
+                BehaviorKind::Patroller,
+                BehaviorKind::Sniper,
+            ],
+            _ => &[
+                BehaviorKind::Chaser,
+                BehaviorKind::Patroller,
+                BehaviorKind::Sniper,
+                BehaviorKind::Guard,
+            ],
+        };
+        let size = (2 + 2 * number as usize).min(MAX_WAVE_SIZE);
+        Wave {
+            number,
+            enemies: kinds.iter().copied().cycle().take(size).collect(),
+        }
+    }
+
+    // 本波的刷新间隔：每波缩短两成，最短为 base 的四分之一
+    pub fn spawn_period(&self, base: Duration) -> Duration {
+        base.mul_f64(0.8f64.powi(self.number as i32 - 1))
+            .max(base / 4)
+    }
+}
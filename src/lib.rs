@@ -2,40 +2,936 @@ pub mod control;
 
 #[cfg(test)]
 mod tests {
-    use crate::control::executor::{Executor, Pose};
+    use crate::control::ai::{chase, line_of_fire, line_of_sight, Chase, Difficulty};
+    use crate::control::behavior::{
+        BehaviorKind, Chaser, EnemyBehavior, EnemyView, Guard, Patroller, Sniper,
+    };
+    use crate::control::board::{Board, BoundaryPolicy, Bounds, Terrain};
+    use crate::control::editor::{Brush, Editor};
+    use crate::control::executor::{
+        Command, Executor, Executors, Heading, MapPlace, Pose, ProgramError, BLOCK_HP,
+        INVULNERABLE_STEPS, PLAYER_HP, PLAYER_LIVES,
+    };
+    use crate::control::game::{Cadence, Cadences, Game, GameState, TICK};
+    use crate::control::generator::{Generator, MapStyle};
+    use crate::control::keys::{Action, KeyBindings, KeyRepeat};
+    use crate::control::level::{Level, SpawnPoint, Spawns};
+    use crate::control::play::{AppMessage, Clock, Scheduler, VirtualClock};
+    use crate::control::powerup::{PowerUp, RAPID_FIRE_DIVISOR};
+    use crate::control::wave::{Wave, WAVE_BONUS};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    // 所有定时事件都推迟到测试跑不到的时候，只剩玩家输入推动局面
+    fn quiet_cadences() -> Cadences {
+        let never = Cadence::new(Duration::from_secs(3600), Duration::from_secs(3600));
+        Cadences {
+            spawn_enemy: never,
+            move_enemies: never,
+            shoot: never,
+            move_shoot: never,
+            spawn_block: never,
+            spawn_pickup: never,
+            ..Cadences::default()
+        }
+    }
 
     #[test]
     fn go_straight() {
         //测试直走功能
-        let mut car = Executor::with_pose(Pose::new(0, 0, 'N'));
-        car.execute("M");
-        car.execute("M");
-        car.execute("M");
-        car.execute("M");
-        car.execute("M");
-        car.execute("M");
-        car.execute("M");
-        car.execute("M");
-        car.execute("M");
-        car.execute("M");
+        let mut car = Executor::with_pose(Pose::new(0, 0, Heading::N));
+        car.execute("MMMMMMMMMM").unwrap();
         //前进会撞到边界，数轴边界设定为5，在control的executor.rs中修改边界
-        assert_eq!(car, Executor::with_pose(Pose::new(0, 5, 'N')));
+        //北方为屏幕上方，y 递减
+        assert_eq!(car, Executor::with_pose(Pose::new(0, -5, Heading::N)));
     }
 
     #[test]
     fn go_circle() {
         //测试转向功能
-        let mut car = Executor::with_pose(Pose::new(0, 0, 'N'));
-        car.execute("L");
-        car.execute("L");
-        car.execute("L");
-        car.execute("L");
-        car.execute("R");
-        car.execute("R");
-        car.execute("R");
-        car.execute("R");
-        car.execute("M");
-        car.execute("L");
-        assert_eq!(car, Executor::with_pose(Pose::new(0, 1, 'W')));
+        let mut car = Executor::with_pose(Pose::new(0, 0, Heading::N));
+        car.execute("LLLLRRRRML").unwrap();
+        assert_eq!(car, Executor::with_pose(Pose::new(0, -1, Heading::W)));
+    }
+
+    #[test]
+    fn program_trace_and_errors() {
+        let mut car = Executor::with_pose(Pose::new(0, 0, Heading::N));
+        let trace = car.execute("MRM").unwrap();
+        assert_eq!(
+            trace,
+            vec![
+                Pose::new(0, -1, Heading::N),
+                Pose::new(0, -1, Heading::E),
+                Pose::new(1, -1, Heading::E),
+            ]
+        );
+
+        //指令串有误时报告出错位置，且不执行任何一步
+        let err = car.execute("MMXM").unwrap_err();
+        assert_eq!(
+            err,
+            ProgramError {
+                index: 2,
+                found: 'X'
+            }
+        );
+        assert_eq!(car, Executor::with_pose(Pose::new(1, -1, Heading::E)));
+    }
+
+    #[test]
+    fn parse_heading_and_command() {
+        assert_eq!("E".parse::<Heading>(), Ok(Heading::E));
+        assert_eq!(Heading::S.to_string(), "S");
+        assert!("X".parse::<Heading>().is_err());
+        assert_eq!("L".parse::<Command>(), Ok(Command::Left));
+        assert_eq!(Command::Move.to_string(), "M");
+        //指令串按单条指令的解析规则逐条读取
+        let all: String = Command::ALL.iter().map(|cmd| cmd.to_string()).collect();
+        assert_eq!(Command::parse_program(&all), Ok(Command::ALL.to_vec()));
+        assert_eq!(
+            Command::parse_program("MTX"),
+            Err(ProgramError {
+                index: 2,
+                found: 'X'
+            })
+        );
+        assert_eq!(
+            Command::parse_program("MT"),
+            Err(ProgramError {
+                index: 1,
+                found: 'T'
+            })
+        );
+    }
+
+    #[test]
+    fn extended_commands() {
+        let mut car = Executor::with_pose(Pose::new(0, 0, Heading::N));
+        car.execute("TR").unwrap();
+        assert_eq!(car.query(), Some((0, 0, Heading::S)));
+
+        //加速模式每次前进两格，倒车模式保持朝向后退
+        car.execute("FM").unwrap();
+        assert_eq!(car.query(), Some((0, 2, Heading::S)));
+        car.execute("BM").unwrap();
+        assert_eq!(car.query(), Some((0, 0, Heading::S)));
+        //再次切换即关闭两种模式
+        car.execute("FBM").unwrap();
+        assert_eq!(car.query(), Some((0, 1, Heading::S)));
+
+        //加速时第二步撞到边界则停在半路
+        let mut car = Executor::with_pose(Pose::new(0, 4, Heading::S));
+        car.execute("FM").unwrap();
+        assert_eq!(car.query(), Some((0, 5, Heading::S)));
+
+        //加速时第二步遇到障碍同样停在半路
+        let mut car = Executor::with_pose(Pose::new(0, 0, Heading::E));
+        car.apply(Command::Fast);
+        car.apply_with(Command::Move, |x, _| x != 2);
+        assert_eq!(car.query(), Some((1, 0, Heading::E)));
+
+        assert_eq!(
+            car.execute("MTX").unwrap_err(),
+            ProgramError {
+                index: 2,
+                found: 'X'
+            }
+        );
+    }
+
+    #[test]
+    fn runtime_board_size() {
+        let bounds = Bounds::new(8, 8);
+        assert_eq!((bounds.x_min, bounds.x_max), (-3, 4));
+        assert_eq!((bounds.width(), bounds.height()), (8, 8));
+
+        //边界检查使用位姿自带的地图范围
+        let mut car = Executor::with_pose(Pose::new(0, 0, Heading::E).with_bounds(bounds));
+        car.execute("MMMMMMMM").unwrap();
+        assert_eq!(car.query(), Some((4, 0, Heading::E)));
+
+        let map = Executors::with_bounds(Bounds::new(64, 48));
+        assert_eq!(map.executors.rows().count(), 48);
+        assert!(map.executors.rows().all(|row| row.len() == 64));
+    }
+
+    #[test]
+    fn boundary_policies() {
+        let bounds = Bounds::new(8, 8);
+
+        //环绕边界：从东侧出界后从西侧进入
+        let wrap = bounds.with_policy(BoundaryPolicy::Wrap);
+        let mut car = Executor::with_pose(Pose::new(4, 0, Heading::E).with_bounds(wrap));
+        car.execute("M").unwrap();
+        assert_eq!(car.query(), Some((-3, 0, Heading::E)));
+
+        //致命边界：驶出地图即被摧毁
+        let lethal = bounds.with_policy(BoundaryPolicy::Lethal);
+        let mut car = Executor::with_pose(Pose::new(0, 3, Heading::N).with_bounds(lethal));
+        car.execute("FMMMM").unwrap();
+        assert!(car.pose().is_none());
+        assert_eq!(car.query(), None);
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let play = |seed| {
+            let mut map = Executors::with_seed(Bounds::default(), seed);
+            for _ in 0..20 {
+                map.enemy_move();
+                map.player_move(Command::Move);
+                map.shoot();
+                map.shoot_move();
+                map.spawn_block();
+                map.spawn();
+            }
+            map
+        };
+        assert_eq!(play(7).executors, play(7).executors);
+        assert_eq!(play(7).point, play(7).point);
+        assert_eq!(play(7).seed(), 7);
+    }
+
+    #[test]
+    fn headless_ticks() {
+        let mut game = Game::new(Executors::with_seed(Bounds::default(), 3));
+        game.start();
+        //第一次 tick 就会刷新敌人
+        assert!(game.tick().contains(&AppMessage::SpawnEnemy));
+
+        //致命边界上的玩家每前进一步就驶出地图丢一条命，命用完时游戏结束，之后 tick 不再推进时间
+        let level: Level = "size = 3x3\npolicy = Lethal\n---\n+^+\n+++\nE++\n"
+            .parse()
+            .unwrap();
+        let mut game = Game::with_cadences(Executors::from_level(level, 3), quiet_cadences());
+        game.start();
+        for _ in 0..10_000 {
+            game.apply_input(Command::Move);
+            game.tick();
+        }
+        assert!(game.is_over());
+        assert_eq!(game.map().lives(), 0);
+        assert_eq!(game.ticks(), u64::from(PLAYER_LIVES) - 1);
+        assert_eq!(game.elapsed(), TICK * game.ticks() as u32);
+        assert!(game.tick().is_empty());
+        assert_eq!(game.elapsed(), TICK * game.ticks() as u32);
+
+        //相同种子和输入得到相同的结果
+        let run = || {
+            let mut game = Game::new(Executors::with_seed(Bounds::default(), 11));
+            game.start();
+            for i in 0..500 {
+                if i % 7 == 0 {
+                    game.apply_input(Command::Left);
+                }
+                game.apply_input(Command::Move);
+                game.tick();
+            }
+            (game.point(), game.ticks(), game.map().executors.clone())
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn scheduler_virtual_clock() {
+        let mut clock = VirtualClock::default();
+        let mut scheduler = Scheduler::new();
+        scheduler.every("fast", 'a', Duration::ZERO, Duration::from_secs(1));
+        scheduler.every("slow", 'b', Duration::from_secs(2), Duration::from_secs(3));
+        assert_eq!(scheduler.poll(&clock), vec!['a']);
+
+        //一次推进多个周期时，按到期顺序返回全部事件，同时到期的按注册顺序
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(
+            scheduler.poll(&clock),
+            vec!['a', 'a', 'b', 'a', 'a', 'a', 'b']
+        );
+
+        //暂停期间时间不前进
+        scheduler.pause();
+        clock.advance(Duration::from_secs(10));
+        assert!(scheduler.poll(&clock).is_empty());
+        scheduler.resume();
+
+        //两倍速下 1 秒真实时间等于 2 秒游戏时间
+        scheduler.set_time_scale(2.0);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(scheduler.poll(&clock), vec!['a', 'a']);
+        assert_eq!(scheduler.now(), Duration::from_secs(7));
+        assert_eq!(clock.now(), Duration::from_secs(16));
+
+        assert!(scheduler.cancel("fast"));
+        scheduler.shutdown();
+        clock.advance(Duration::from_secs(60));
+        assert!(scheduler.poll(&clock).is_empty());
+    }
+
+    #[test]
+    fn game_state_machine() {
+        let mut game = Game::new(Executors::with_seed(Bounds::default(), 5));
+        assert_eq!(game.state(), GameState::MainMenu);
+        //主菜单下时间不前进
+        assert!(game.tick().is_empty());
+        game.start();
+        assert_eq!(game.state(), GameState::Running);
+        game.tick();
+
+        //暂停时所有定时事件一起冻结
+        game.pause();
+        let board = game.map().executors.clone();
+        let elapsed = game.elapsed();
+        for _ in 0..100 {
+            assert!(game.tick().is_empty());
+        }
+        assert_eq!(game.map().executors, board);
+        assert_eq!(game.elapsed(), elapsed);
+        game.resume();
+
+        //一直前进直到被击中
+        while !game.is_over() && game.ticks() < 100_000 {
+            game.apply_input(Command::Move);
+            game.tick();
+        }
+        assert_eq!(game.state(), GameState::GameOver);
+
+        //重开后地图和调度器都被重置
+        game.restart();
+        assert_eq!(game.state(), GameState::Running);
+        assert_eq!(game.point(), 0);
+        assert_eq!(game.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn game_follows_clock() {
+        let mut clock = VirtualClock::default();
+        let mut game = Game::new(Executors::with_seed(Bounds::default(), 9));
+        //主菜单停留的时间不计入游戏时间
+        game.poll(&clock);
+        clock.advance(Duration::from_secs(30));
+        game.poll(&clock);
+        game.start();
+        //一帧之内到期的所有事件都会被处理
+        clock.advance(Duration::from_secs(6));
+        let handled = game.poll(&clock);
+        assert_eq!(
+            handled
+                .iter()
+                .filter(|msg| **msg == AppMessage::MoveEnemies)
+                .count(),
+            4
+        );
+        assert_eq!(game.elapsed(), Duration::from_secs(6));
+        assert!(game.until_next().is_some());
+
+        //暂停期间流逝的真实时间不计入游戏时间
+        game.pause();
+        clock.advance(Duration::from_secs(60));
+        assert!(game.poll(&clock).is_empty());
+        assert!(game.until_next().is_none());
+        game.resume();
+        game.poll(&clock);
+        assert_eq!(game.elapsed(), Duration::from_secs(6));
+    }
+
+    #[test]
+    fn key_bindings() {
+        use eframe::egui::Key;
+
+        let mut keys = KeyBindings::default();
+        assert_eq!(keys.action(Key::W), Some(Action::Move));
+        assert_eq!(keys.action(Key::Space), Some(Action::Fire));
+
+        //同一按键改绑后只属于新的动作
+        keys.bind(Action::Fire, Key::W);
+        assert_eq!(keys.action(Key::W), Some(Action::Fire));
+        assert_eq!(keys.keys(Action::Move), &[Key::ArrowUp]);
+
+        //配置文件格式可以原样读回
+        let text = keys.to_string();
+        assert_eq!(text.parse::<KeyBindings>(), Ok(keys));
+        assert!("Jump = Space".parse::<KeyBindings>().is_err());
+        assert!("Fire = NoSuchKey".parse::<KeyBindings>().is_err());
+
+        //按住按键时按固定间隔重复，而不是每帧触发
+        let mut repeat = KeyRepeat::new(Duration::from_millis(200));
+        let ms = Duration::from_millis;
+        assert_eq!(repeat.update(ms(0), &[Action::Move]), vec![Action::Move]);
+        assert!(repeat.update(ms(16), &[Action::Move]).is_empty());
+        assert!(repeat.update(ms(150), &[Action::Move]).is_empty());
+        assert_eq!(repeat.update(ms(200), &[Action::Move]), vec![Action::Move]);
+        //松开后再按立即触发
+        assert!(repeat.update(ms(210), &[]).is_empty());
+        assert_eq!(repeat.update(ms(220), &[Action::Move]), vec![Action::Move]);
+    }
+
+    #[test]
+    fn player_fire_cooldown() {
+        //开局的敌人既不移动也不开火，游戏不会中途结束
+        let map = Executors::with_seed(Bounds::default(), 1);
+        let mut game = Game::with_cadences(map, quiet_cadences());
+        assert!(!game.fire());
+        game.start();
+        assert!(game.fire());
+        //冷却期间无法再次开火
+        assert!(!game.fire());
+        assert_eq!(game.fire_cooldown_left(), game.cadences().fire_cooldown);
+        for _ in 0..5 {
+            game.tick();
+        }
+        assert_eq!(game.fire_cooldown_left(), Duration::ZERO);
+        assert!(game.fire());
+        assert_eq!(game.state(), GameState::Running);
+    }
+
+    #[test]
+    fn enemies_fire_on_their_own_schedule() {
+        let mut map = Executors::with_seed(Bounds::default(), 2);
+        let bullets = |map: &Executors| {
+            map.executors
+                .rows()
+                .flatten()
+                .filter(|cell| matches!(cell, MapPlace::Shoot(_)))
+                .count()
+        };
+        //敌人都看不到出生点的玩家，装填完毕也不会开火，玩家也不会自动开火
+        for _ in 0..17 {
+            map.shoot();
+        }
+        assert_eq!(bullets(&map), 0);
+        //玩家走到右侧敌人的正下方，该敌人立刻转向并开火
+        map.player_move(Command::Right);
+        for _ in 0..4 {
+            map.player_move(Command::Move);
+        }
+        assert_eq!(
+            line_of_sight(&map.executors, (4, -5), (4, 5)),
+            Some(Heading::S)
+        );
+        assert_eq!(line_of_sight(&map.executors, (-4, -5), (4, 5)), None);
+        map.shoot();
+        assert_eq!(bullets(&map), 1);
+        assert!(matches!(map.executors[(4, -4)], MapPlace::Shoot(_)));
+    }
+
+    #[test]
+    fn line_of_sight_blocked_by_blocks() {
+        let mut board = Board::new(Bounds::new(7, 7));
+        assert_eq!(line_of_sight(&board, (0, -3), (0, 3)), Some(Heading::S));
+        assert_eq!(line_of_sight(&board, (3, 0), (-3, 0)), Some(Heading::W));
+        //不在同一行或列时看不到
+        assert_eq!(line_of_sight(&board, (1, -3), (0, 3)), None);
+        board[(0, 1)] = MapPlace::Block(BLOCK_HP);
+        assert_eq!(line_of_sight(&board, (0, -3), (0, 3)), None);
+        //环面地图上可以从另一侧看到
+        let mut board = Board::new(Bounds::new(7, 7).with_policy(BoundaryPolicy::Wrap));
+        board[(0, 1)] = MapPlace::Block(BLOCK_HP);
+        assert_eq!(line_of_sight(&board, (0, -3), (0, 3)), Some(Heading::N));
+    }
+
+    #[test]
+    fn lives_and_invulnerability() {
+        let bounds = Bounds::default().with_policy(BoundaryPolicy::Lethal);
+        let mut map = Executors::with_seed(bounds, 4);
+        assert_eq!((map.player_hp(), map.lives()), (PLAYER_HP, PLAYER_LIVES));
+        //玩家出生在底边，掉头前进会驶出地图，失去一条命并在出生点重生
+        map.player_move(Command::TurnRound);
+        map.player_move(Command::Move);
+        assert_eq!(map.lives(), PLAYER_LIVES - 1);
+        assert!(!map.is_lose);
+        assert!(map.is_invulnerable());
+        assert!(matches!(
+            map.executors[(0, bounds.y_max)],
+            MapPlace::Player(_)
+        ));
+        //无敌时间按子弹移动的步数递减
+        for _ in 0..INVULNERABLE_STEPS {
+            map.shoot_move();
+        }
+        assert!(!map.is_invulnerable());
+        for _ in 1..PLAYER_LIVES {
+            map.player_move(Command::TurnRound);
+            map.player_move(Command::Move);
+        }
+        assert_eq!(map.lives(), 0);
+        assert!(map.is_lose);
+    }
+
+    #[test]
+    fn chase_around_blocks() {
+        let mut board = Board::new(Bounds::new(7, 7));
+        //敌人在左上角，玩家在右下角，中间一堵墙挡住同行同列的射线
+        for y in -3..=2 {
+            board[(0, y)] = MapPlace::Block(BLOCK_HP);
+        }
+        let (enemy, player) = ((-3, -3), (3, 3));
+        board[enemy] = MapPlace::Enemy(Executor::with_pose(Pose::new(-3, -3, Heading::S)));
+        board[player] = MapPlace::Player(Executor::with_pose(Pose::new(3, 3, Heading::N)));
+        assert_eq!(chase(&board, enemy, player), Some(Chase::Step(Heading::S)));
+        //已经与玩家同列且中间无遮挡时只需转向
+        assert_eq!(chase(&board, (3, -3), player), Some(Chase::Aim(Heading::S)));
+        //被完全围住时找不到路
+        board[(-2, -3)] = MapPlace::Block(BLOCK_HP);
+        board[(-3, -2)] = MapPlace::Block(BLOCK_HP);
+        assert_eq!(chase(&board, enemy, player), None);
+        assert!(Difficulty::Easy.aggression() < Difficulty::Hard.aggression());
+    }
+
+    #[test]
+    fn enemy_behaviors() {
+        let mut board = Board::new(Bounds::new(7, 7));
+        board[(0, -2)] = MapPlace::Block(BLOCK_HP);
+        let mut rng = StdRng::seed_from_u64(0);
+        let view = |board, position, heading, player| EnemyView {
+            board,
+            position,
+            heading,
+            player,
+            base: None,
+        };
+        //巡逻者一路前进，被挡住就掉头
+        let mut patroller = Patroller;
+        let v = view(&board, (0, 0), Heading::N, (3, 3));
+        assert_eq!(patroller.next_command(&v, &mut rng), Some(Command::Move));
+        let v = view(&board, (0, -1), Heading::N, (3, 3));
+        assert_eq!(
+            patroller.next_command(&v, &mut rng),
+            Some(Command::TurnRound)
+        );
+        //狙击手不移动，只向正前方开火
+        let mut sniper = Sniper;
+        let v = view(&board, (3, -3), Heading::S, (3, 3));
+        assert_eq!(sniper.next_command(&v, &mut rng), None);
+        assert_eq!(sniper.aim(&v), Some(Heading::S));
+        let v = view(&board, (3, -3), Heading::W, (3, 3));
+        assert_eq!(sniper.aim(&v), None);
+        //追击者已瞄准看得到的玩家时原地不动，不会随机走开
+        let mut chaser = Chaser { aggression: 1.0 };
+        let v = view(&board, (3, -3), Heading::S, (3, 3));
+        for _ in 0..10 {
+            assert_eq!(chaser.next_command(&v, &mut rng), None);
+        }
+        //守卫在玩家远离时回到岗位，玩家靠近时追击
+        let mut guard = Guard { post: (-3, -3) };
+        let v = view(&board, (-3, 0), Heading::N, (3, 3));
+        assert_eq!(guard.next_command(&v, &mut rng), Some(Command::Move));
+        let v = view(&board, (-3, -3), Heading::N, (3, 3));
+        assert_eq!(guard.next_command(&v, &mut rng), None);
+        let v = view(&board, (-3, -3), Heading::N, (-1, -3));
+        assert_eq!(guard.next_command(&v, &mut rng), Some(Command::Right));
+        //关卡数据按名称选择行为
+        assert_eq!("Sniper".parse(), Ok(BehaviorKind::Sniper));
+        assert!("Boss".parse::<BehaviorKind>().is_err());
+    }
+
+    #[test]
+    fn waves_escalate() {
+        let first = Wave::new(1);
+        let later = Wave::new(4);
+        assert!(later.enemies.len() > first.enemies.len());
+        assert!(later.enemies.contains(&BehaviorKind::Guard));
+        let base = Duration::from_secs(10);
+        assert!(later.spawn_period(base) < first.spawn_period(base));
+        assert_eq!(first.spawn_period(base), base);
+
+        //一格宽的走廊：敌人只能出生在玩家右侧第三格，并且不会移动和开火，
+        //玩家转身打掉它后转回来，清空一波的过程与随机数无关
+        const LIMIT: u32 = 1_000;
+        let corridor: Level = "size = 4x1\n---\n^++E\n".parse().unwrap();
+        let cadences = Cadences {
+            spawn_enemy: Cadence::new(Duration::ZERO, Duration::from_secs(1)),
+            move_shoot: Cadences::default().move_shoot,
+            ..quiet_cadences()
+        };
+        //过程与种子无关，多换几个种子验证
+        for seed in 1..=8 {
+            let mut game =
+                Game::with_cadences(Executors::from_level(corridor.clone(), seed), cadences);
+            assert_eq!(game.map().enemies_remaining(), first.enemies.len());
+            game.start();
+            let mut ticks = 0;
+            while !game.map().is_wave_cleared() {
+                ticks += 1;
+                assert!(
+                    ticks <= LIMIT,
+                    "wave 1 not cleared after {} ticks (seed {})",
+                    LIMIT,
+                    seed
+                );
+                //道具不会落在出生点上，玩家始终停在起点
+                let Some(MapPlace::Player(player)) = game.map().executors.get(-1, 0) else {
+                    panic!("the player left the start cell");
+                };
+                let (_, _, heading) = player.query().unwrap();
+                let enemy = matches!(game.map().executors[(2, 0)], MapPlace::Enemy(_));
+                match (heading, enemy) {
+                    (Heading::N, true) => game.apply_input(Command::Right),
+                    (Heading::E, true) => {
+                        game.fire();
+                    }
+                    (Heading::E, false) => game.apply_input(Command::Left),
+                    _ => {}
+                }
+                game.tick();
+            }
+            assert_eq!(game.point(), first.enemies.len() as i32);
+            //清空后休息一段时间才开始下一波
+            let cleared_at = game.elapsed();
+            assert_eq!(game.wave(), 1);
+            ticks = 0;
+            while game.wave() == 1 {
+                ticks += 1;
+                assert!(
+                    ticks <= LIMIT,
+                    "wave 2 not started after {} ticks (seed {})",
+                    LIMIT,
+                    seed
+                );
+                game.tick();
+            }
+            let breather = game.elapsed() - cleared_at;
+            assert!(breather >= game.cadences().breather);
+            assert!(breather <= game.cadences().breather + TICK);
+            assert_eq!(game.point(), first.enemies.len() as i32 + WAVE_BONUS);
+            assert_eq!(game.map().enemies_remaining(), Wave::new(2).enemies.len());
+        }
+    }
+
+    #[test]
+    fn spawn_safety_and_rotation() {
+        let bounds = Bounds::new(9, 9);
+        let spawns = Spawns {
+            player: vec![(0, 4)],
+            enemies: vec![SpawnPoint::new(0, -4), SpawnPoint::new(3, 3)],
+        };
+        let mut map = Executors::with_spawns(bounds, 0, spawns);
+        for y in bounds.y_min..=bounds.y_max {
+            for x in bounds.x_min..=bounds.x_max {
+                if let MapPlace::Block(_) = map.executors[(x, y)] {
+                    map.executors[(x, y)] = MapPlace::Place;
+                }
+            }
+        }
+        //(0, -4) 在玩家正前方的火力线上，开局只有 (3, 3) 刷出敌人
+        assert!(matches!(map.executors[(0, 4)], MapPlace::Player(_)));
+        assert!(matches!(map.executors[(3, 3)], MapPlace::Enemy(_)));
+        assert_eq!(map.executors[(0, -4)], MapPlace::Place);
+        assert!(!map.is_safe_spawn(0, -4));
+        assert!(!map.is_safe_spawn(1, 3));
+        assert!(map.is_safe_spawn(-3, -3));
+        //两个出生点都不能用时，改在轮到的出生点附近最近的安全空地刷新
+        map.spawn();
+        assert!(matches!(map.executors[(-1, -4)], MapPlace::Enemy(_)));
+        //玩家转向后火力线让开，出生点恢复可用
+        map.player_move(Command::Right);
+        assert!(map.is_safe_spawn(0, -4));
+    }
+
+    #[test]
+    fn level_files() {
+        let text = include_str!("../levels/arena.lvl");
+        let level: Level = text.parse().unwrap();
+        assert_eq!((level.bounds.width(), level.bounds.height()), (13, 11));
+        assert_eq!(level.spawns.player, vec![(0, 5), (-5, 5), (5, 5)]);
+        assert_eq!(level.spawns.enemies.len(), 3);
+        assert_eq!(level.spawns.enemies[1].behavior, Some(BehaviorKind::Sniper));
+        //写出后再读入得到同样的关卡
+        assert_eq!(level.to_string().parse::<Level>(), Ok(level.clone()));
+
+        let map = Executors::from_level(level.clone(), 1);
+        assert_eq!(map.executors[(-1, -1)], MapPlace::Block(BLOCK_HP));
+        assert!(matches!(map.executors[(0, -5)], MapPlace::Enemy(_)));
+        assert_eq!(map.to_level(), level);
+
+        //错误信息带有行号和列号
+        let err = |text: &str| text.parse::<Level>().unwrap_err().to_string();
+        assert_eq!(
+            err("size = 3x2\n---\n+^+\n+x+\n"),
+            "line 4, column 2: unknown glyph 'x'"
+        );
+        assert_eq!(
+            err("size = 3x2\n---\n+^+\n++\n"),
+            "line 4, column 3: expected 3 columns"
+        );
+        assert_eq!(
+            err("size = 3x2\npolicy = Bounce\n---\n"),
+            "line 2, column 10: unknown policy \"Bounce\""
+        );
+        assert_eq!(
+            err("size = 3x2\n---\n+++\n+++\n"),
+            "line 2, column 1: the map has no player"
+        );
+    }
+
+    #[test]
+    fn level_editor() {
+        let mut editor = Editor::new(Level::new(Bounds::new(5, 5)));
+        assert_eq!(editor.level().validate(), Ok(()));
+        //用方块把右上角的敌人出生点围起来，检查会发现它走不到
+        let enemy = editor.level().spawns.enemies[0];
+        assert_eq!((enemy.x, enemy.y), (0, -2));
+        editor.paint(-1, -2, Brush::Block);
+        editor.paint(1, -2, Brush::Block);
+        editor.paint(0, -1, Brush::Block);
+        assert_eq!(
+            editor.level().validate(),
+            Err("spawn point (0, -2) is unreachable".to_string())
+        );
+        assert!(editor.undo());
+        assert_eq!(editor.level().validate(), Ok(()));
+        assert!(editor.redo());
+        assert!(!editor.redo());
+        assert!(editor.level().validate().is_err());
+        //再次点击开局位置会让玩家转向
+        editor.paint(0, 2, Brush::PlayerStart);
+        assert_eq!(editor.level().heading, Heading::E);
+        editor.paint(2, 2, Brush::PlayerStart);
+        assert_eq!(editor.level().spawns.player, vec![(2, 2)]);
+        //缩小地图会丢弃范围外的内容
+        editor.resize(3, 3);
+        assert_eq!(editor.level().bounds.width(), 3);
+        assert!(editor.level().spawns.player.is_empty());
+        assert_eq!(
+            editor.level().validate(),
+            Err("the map has no player".to_string())
+        );
+    }
+
+    #[test]
+    fn generated_maps_stay_connected() {
+        for style in MapStyle::ALL {
+            for (width, height) in [(8, 8), (13, 11), (21, 15)] {
+                let bounds = Bounds::new(width, height);
+                for seed in 0..5 {
+                    let generator = Generator::new(style).with_density(0.4);
+                    let mut map =
+                        Executors::with_generator(bounds, seed, Spawns::new(bounds), generator);
+                    assert_eq!(map.to_level().validate(), Ok(()), "{:?} {}", style, seed);
+                    let level = map.to_level();
+                    if style == MapStyle::Symmetric {
+                        assert!(level.blocks.iter().all(|((x, y), _)| level
+                            .block_at(bounds.x_min + bounds.x_max - x, *y)
+                            .is_some()));
+                    }
+                    //刷新方块也不会把出生点隔开
+                    for _ in 0..20 {
+                        map.spawn_block();
+                    }
+                    assert_eq!(map.to_level().validate(), Ok(()), "{:?} {}", style, seed);
+                }
+            }
+        }
+        //迷宫按密度拆墙
+        let bounds = Bounds::new(21, 15);
+        let maze = Generator::new(MapStyle::Maze).with_density(0.2);
+        let map = Executors::with_generator(bounds, 1, Spawns::new(bounds), maze);
+        assert_eq!(map.to_level().blocks.len(), bounds.area() / 5);
+    }
+
+    #[test]
+    fn terrain_rules() {
+        let text = "size = 7x5\n---\nE+++++E\n+++=+++\n++%+#++\n+~+_+++\n+++^+++\n";
+        let level: Level = text.parse().unwrap();
+        assert_eq!(level.steel, vec![(0, -1)]);
+        assert_eq!(level.terrain_at(0, 1), Terrain::Ice);
+        assert_eq!(level.to_string().parse::<Level>(), Ok(level.clone()));
+        let mut map = Executors::from_level(level.clone(), 0);
+        //停在冰面上会再滑一格
+        map.player_move(Command::Move);
+        assert!(matches!(map.executors[(0, 0)], MapPlace::Player(_)));
+        //钢块打不掉，砖块耐久度耗尽后被摧毁
+        map.player_shoot();
+        assert_eq!(map.executors[(0, -1)], MapPlace::Steel);
+        map.player_move(Command::Right);
+        for _ in 0..BLOCK_HP {
+            map.player_shoot();
+        }
+        assert_eq!(map.executors[(1, 0)], MapPlace::Place);
+        //树林中的单位看不到
+        assert_eq!(
+            line_of_sight(&map.executors, (-3, 0), (0, 0)),
+            Some(Heading::E)
+        );
+        map.player_move(Command::TurnRound);
+        map.player_move(Command::Move);
+        assert!(matches!(map.executors[(-1, 0)], MapPlace::Player(_)));
+        assert_eq!(line_of_sight(&map.executors, (-3, 0), (-1, 0)), None);
+        //停在树林里也能保存，写回的是原来的开局位置，树林不会丢
+        let path = std::env::temp_dir().join("terrain_rules.level");
+        map.save(&path).unwrap();
+        let saved = Executors::load(&path).unwrap().to_level();
+        assert_eq!(saved, map.to_level());
+        assert_eq!(saved.terrain_at(-1, 0), Terrain::Forest);
+        assert_eq!(saved.spawns.player[0], (0, 2));
+        //读取和保存一样拒绝无法游玩的关卡
+        std::fs::write(&path, "size = 3x1\n---\n^++\n").unwrap();
+        assert_eq!(
+            Executors::load(&path).map(|_| ()).map_err(|e| e.kind()),
+            Err(std::io::ErrorKind::InvalidData)
+        );
+        std::fs::remove_file(&path).unwrap();
+        //水面挡住坦克，但子弹可以飞过
+        map.player_move(Command::Left);
+        map.player_move(Command::Move);
+        map.player_move(Command::Right);
+        map.player_move(Command::Move);
+        assert!(matches!(map.executors[(-1, 1)], MapPlace::Player(_)));
+        map.player_shoot();
+        map.shoot_move();
+        assert!(matches!(map.executors[(-3, 1)], MapPlace::Shoot(_)));
+        //出生点不能在水里
+        let mut wet = level;
+        wet.terrain.push(((3, -2), Terrain::Water));
+        assert!(wet.validate().is_err());
+    }
+
+    #[test]
+    fn block_durability() {
+        let text = "size = 5x5\n---\nE+++E\n+++++\n+1#9+\n+++++\n++^++\n";
+        let level: Level = text.parse().unwrap();
+        assert_eq!(level.block_at(-1, 0), Some(1));
+        assert_eq!(level.block_at(0, 0), Some(BLOCK_HP));
+        assert_eq!(level.block_at(1, 0), Some(9));
+        assert_eq!(level.to_string().parse::<Level>(), Ok(level.clone()));
+        //子弹每次命中扣一点耐久度，受损的砖块照原样写回关卡
+        let mut map = Executors::from_level(level, 0);
+        map.player_move(Command::Move);
+        map.player_shoot();
+        assert_eq!(map.executors[(0, 0)], MapPlace::Block(BLOCK_HP - 1));
+        map.player_move(Command::Back);
+        map.player_move(Command::Move);
+        map.player_move(Command::Back);
+        map.player_shoot();
+        map.shoot_move();
+        assert_eq!(map.executors[(0, 0)], MapPlace::Block(BLOCK_HP - 2));
+        assert_eq!(map.to_level().block_at(0, 0), Some(1));
+        //损坏程度按出现时的耐久度计算
+        assert_eq!(map.block_durability(0, 0), Some((1, BLOCK_HP)));
+        assert_eq!(map.block_durability(1, 0), Some((9, 9)));
+        assert_eq!(map.block_durability(0, 1), None);
+        map.player_shoot();
+        map.shoot_move();
+        assert_eq!(map.executors[(0, 0)], MapPlace::Place);
+        //编辑器中再次点击砖块会改变耐久度
+        let mut editor = Editor::default();
+        editor.paint(0, 0, Brush::Block);
+        editor.paint(0, 0, Brush::Block);
+        assert_eq!(editor.level().block_at(0, 0), Some(BLOCK_HP + 1));
+    }
+
+    #[test]
+    fn power_ups() {
+        let level: Level = "size = 5x5\n---\nE+++E\n+++++\n+++++\n+++++\n++^++\n"
+            .parse()
+            .unwrap();
+        let mut map = Executors::from_level(level, 0);
+        map.executors[(0, 1)] = MapPlace::Pickup(PowerUp::Shield);
+        map.executors[(0, 0)] = MapPlace::Pickup(PowerUp::Freeze);
+        map.executors[(0, -1)] = MapPlace::Pickup(PowerUp::ExtraLife);
+        map.executors[(0, -2)] = MapPlace::Pickup(PowerUp::Bomb);
+        map.player_move(Command::Move);
+        map.player_move(Command::Move);
+        assert!(map.has_effect(PowerUp::Shield) && map.has_effect(PowerUp::Freeze));
+        //冻结期间敌人既不移动也不开火
+        let frozen = map.executors.clone();
+        map.enemy_move();
+        map.shoot();
+        assert_eq!(map.executors, frozen);
+        map.player_move(Command::Move);
+        assert_eq!(map.lives(), PLAYER_LIVES + 1);
+        //炸弹消灭场上所有敌人
+        map.player_move(Command::Move);
+        assert_eq!(map.point, 2);
+        assert_eq!(map.executors[(-2, -2)], MapPlace::Place);
+        assert_eq!(map.executors[(2, -2)], MapPlace::Place);
+        //持续效果到时消失
+        map.elapse(Duration::from_secs(5));
+        assert!(map.has_effect(PowerUp::Shield) && !map.has_effect(PowerUp::Freeze));
+        map.elapse(Duration::from_secs(5));
+        assert!(map.effects().is_empty());
+        //道具吸收子弹，对敌人来说也挡住视线、火力线和道路
+        map.executors[(0, 0)] = MapPlace::Pickup(PowerUp::Shield);
+        assert!(map.executors.is_obstacle(0, 0));
+        assert_eq!(line_of_sight(&map.executors, (0, 2), (0, -2)), None);
+        assert_eq!(line_of_fire(&map.executors, (0, 2), (0, -2)), None);
+        map.player_move(Command::TurnRound);
+        map.player_shoot();
+        map.shoot_move();
+        map.shoot_move();
+        assert_eq!(map.executors[(0, 0)], MapPlace::Pickup(PowerUp::Shield));
+        assert_eq!(map.executors[(0, 1)], MapPlace::Place);
+        map.player_move(Command::TurnRound);
+
+        //连射缩短开火冷却，按当前地图重开时玩家回到开局位置 (0, 2)
+        let mut map = Executors::from_level(map.to_level(), 0);
+        map.executors[(0, 1)] = MapPlace::Pickup(PowerUp::RapidFire);
+        let mut game = Game::new(map);
+        game.start();
+        game.apply_input(Command::Move);
+        assert!(game.fire());
+        assert_eq!(
+            game.fire_cooldown_left(),
+            game.cadences().fire_cooldown / RAPID_FIRE_DIVISOR
+        );
+    }
+
+    #[test]
+    fn base_defense() {
+        let mut map = Executors::from_level(Level::new(Bounds::new(7, 5)), 0).with_base();
+        //基地放在玩家左边隔一格，四周围上砖墙
+        assert_eq!(map.base(), Some((-2, 2)));
+        for wall in [(-3, 2), (-1, 2), (-3, 1), (-2, 1), (-1, 1)] {
+            assert_eq!(map.executors[wall], MapPlace::Block(BLOCK_HP));
+        }
+        let level = map.to_level();
+        assert_eq!(level.validate(), Ok(()));
+        assert_eq!(
+            level.to_string().parse::<Level>().map(|level| level.base),
+            Ok(Some((-2, 2)))
+        );
+        assert_eq!(map.replay(1).base(), Some((-2, 2)));
+        //敌人看不到墙后的基地，但可以打穿砖墙
+        assert_eq!(line_of_sight(&map.executors, (-2, -2), (-2, 2)), None);
+        assert_eq!(
+            line_of_fire(&map.executors, (-2, -2), (-2, 2)),
+            Some(Heading::S)
+        );
+        let view = EnemyView {
+            board: &map.executors,
+            position: (-2, -2),
+            heading: Heading::E,
+            player: (0, 2),
+            base: map.base(),
+        };
+        assert_eq!(Sniper.aim(&view), None);
+        assert_eq!(Chaser::new(Difficulty::Hard).aim(&view), Some(Heading::S));
+        //任何子弹打中基地都会输掉，即使玩家还活着
+        map.player_move(Command::Left);
+        for _ in 0..BLOCK_HP {
+            map.player_shoot();
+        }
+        map.player_shoot();
+        map.shoot_move();
+        assert!(map.is_lose && map.is_base_destroyed());
+        //被摧毁的基地不会写回关卡
+        assert_eq!(map.to_level().base, None);
+        assert_eq!(map.lives(), PLAYER_LIVES);
+        //打穿砖墙后贴着基地开火，游戏当即结束
+        let map = Executors::from_level(Level::new(Bounds::new(7, 5)), 0).with_base();
+        let mut game = Game::with_cadences(map, quiet_cadences());
+        game.start();
+        game.apply_input(Command::Left);
+        for _ in 0..BLOCK_HP {
+            game.advance(game.fire_cooldown_left());
+            assert!(game.fire());
+        }
+        assert_eq!(game.map().executors[(-1, 2)], MapPlace::Place);
+        game.apply_input(Command::Move);
+        game.advance(game.fire_cooldown_left());
+        assert_eq!(game.state(), GameState::Running);
+        assert!(game.fire());
+        assert!(game.map().is_base_destroyed());
+        assert_eq!(game.state(), GameState::GameOver);
     }
 }
@@ -1,5 +1,4 @@
-mod control;
-use control::gui::create_gui;
+use software::control::gui::create_gui;
 
 fn main() -> eframe::Result {
     create_gui()